@@ -0,0 +1,216 @@
+use std::collections::{HashMap, HashSet};
+use crate::interpret::{Goal, Condition, Action};
+use crate::error::Span;
+use crate::statement::{Statement, Variable, BinaryOperator, Symbols};
+use crate::statement::invert::non_invertible;
+
+// A static pass over the rules of a goal that finds variables which can never be bound. The head binds each of its variables,
+// an `=` condition binds the variables on one side once those on the other side are bound, `X in L` binds `X` once `L` is
+// bound and an index expression `P[I]` binds `I` once `P` is bound. Conditions may be written in any order (they are
+// suspended until their variables are bound), so bindings are propagated until nothing changes. The conditions of `&&` are
+// treated as separate conditions and `||` binds the variables that both of its conditions bind. Each alternative of a rule
+// is analysed as if it were a separate rule with the same actions.
+
+/// A problem with a rule that is found without evaluating it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding {
+    Unbound(Variable, Statement, Span), // a variable that can never be bound is used by the condition or action written at the span
+    Singleton(Variable, Span),          // a variable that is only used once (where it is written), it should be `_`
+}
+
+/// finds the problems with each rule of `goal`, variables whose name begins with `_` may be used once.
+pub fn analyse(goal : &Goal, symbols : &Symbols) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let head : Vec<&Statement> = goal.head().arguments().iter().collect();
+    let shared = variables(&head);
+    let alternatives : Vec<(&[Condition], &[Action])> = goal.body().iter()
+        .flat_map(|rule| rule.alternatives().iter().map(|conditions| (conditions.as_slice(), rule.actions())))
+        .collect();
+    for (conditions, actions) in alternatives.iter() {
+        findings.extend(unbound(&shared, conditions, actions));
+    }
+    // variables of the head are shared by every rule, any others are local to their rule.
+    let mut counts = HashMap::new();
+    count(&head, &mut counts);
+    alternatives.iter().for_each(|(conditions, actions)| count(&statements(conditions, actions), &mut counts));
+    let mut singletons : Vec<Variable> = counts.into_iter().filter(|(x, n)| *n == 1 && shared.contains(x)).map(|(x, _)| x).collect();
+    for (conditions, actions) in alternatives.iter() {
+        let mut counts = HashMap::new();
+        count(&statements(conditions, actions), &mut counts);
+        singletons.extend(counts.into_iter().filter(|(x, n)| *n == 1 && !shared.contains(x)).map(|(x, _)| x));
+    }
+    // a variable that can never be bound is already reported
+    let unbound : HashSet<Variable> = findings.iter().filter_map(|x| match x { Finding::Unbound(x, _, _) => Some(*x), _ => None }).collect();
+    singletons.retain(|x| !symbols.name(x.name).starts_with('_') && !unbound.contains(x));
+    singletons.sort_by_key(|x| x.slot);
+    singletons.dedup();
+    findings.extend(singletons.into_iter().map(|x| Finding::Singleton(x, goal.variable_span(&x).clone())));
+    return findings;
+}
+
+// finds the conditions and actions of a rule (with these conditions) that use a variable that can never be bound.
+fn unbound(head : &HashSet<Variable>, conditions : &[Condition], actions : &[Action]) -> Vec<Finding> {
+    let bound = bindings(&statements(conditions, actions), head.clone());
+    return conditions.iter().map(|x| (&x.0, &x.1)).chain(actions.iter().map(|x| (&x.0, &x.1)))
+        .filter_map(|(statement, span)| missing(statement, &bound).map(|variable| Finding::Unbound(variable, statement.clone(), span.clone())))
+        .collect();
+}
+
+// the variables that are bound by `statements` given that those in `bound` are.
+fn bindings(statements : &[&Statement], mut bound : HashSet<Variable>) -> HashSet<Variable> {
+    loop {
+        let before = bound.len();
+        statements.iter().for_each(|statement| bind(statement, &mut bound));
+        if bound.len() == before {
+            return bound;
+        }
+    }
+}
+
+// adds the variables that a condition binds to `bound`.
+fn bind(condition : &Statement, bound : &mut HashSet<Variable>) {
+    match condition {
+        Statement::BinaryOperator(BinaryOperator::And(lhs, rhs)) => {
+            bind(lhs, bound);
+            bind(rhs, bound);
+        },
+        Statement::BinaryOperator(BinaryOperator::Or(lhs, rhs)) => {
+            let (left, right) = (bindings(&[lhs], bound.clone()), bindings(&[rhs], bound.clone()));
+            bound.extend(left.intersection(&right));
+        },
+        Statement::BinaryOperator(BinaryOperator::Equal(lhs, rhs)) => {
+            bind_indices(condition, bound);
+            if required(lhs).is_subset(bound) && solvable(rhs) {
+                bound.extend(variables(&[rhs]));
+            }
+            if required(rhs).is_subset(bound) && solvable(lhs) {
+                bound.extend(variables(&[lhs]));
+            }
+        },
+        Statement::BinaryOperator(BinaryOperator::In(item, collection)) => {
+            bind_indices(condition, bound);
+            if required(collection).is_subset(bound) && solvable(item) {
+                bound.extend(variables(&[item]));
+            }
+        },
+        x => bind_indices(x, bound),
+    }
+}
+
+// the first variable (by slot) that `statement` needs but is not bound, the conditions of `||` may use what they bind.
+fn missing(statement : &Statement, bound : &HashSet<Variable>) -> Option<Variable> {
+    // a variable on one side of `=` that is never bound is fine unless it is needed to evaluate an arithmetic expression
+    let needed = match statement {
+        Statement::BinaryOperator(BinaryOperator::And(lhs, rhs)) => return missing(lhs, bound).or_else(|| missing(rhs, bound)),
+        Statement::BinaryOperator(BinaryOperator::Or(lhs, rhs)) => return [lhs, rhs].into_iter().find_map(|x| missing(x, &bindings(&[x], bound.clone()))),
+        Statement::BinaryOperator(BinaryOperator::Equal(lhs, rhs)) => arithmetic(lhs).union(&arithmetic(rhs)).copied().collect(),
+        Statement::BinaryOperator(BinaryOperator::In(item, collection)) => arithmetic(item).union(&required(collection)).copied().collect(),
+        x => required(x),
+    };
+    return needed.difference(bound).min_by_key(|x| x.slot).copied();
+}
+
+// an index expression `P[I]` binds the variables of `I` (by enumerating the positions of `P`) once `P` is bound.
+fn bind_indices(statement : &Statement, bound : &mut HashSet<Variable>) {
+    if let Statement::BinaryOperator(BinaryOperator::Index(collection, key)) = statement {
+        bind_indices(collection, bound);
+        if required(collection).is_subset(bound) {
+            bound.extend(variables(&[key]));
+        }
+        bind_indices(key, bound);
+        return;
+    }
+    statement.children().into_iter().for_each(|x| bind_indices(x, bound));
+}
+
+// the variables that must be bound before `statement` can be evaluated, those used as an index are bound by evaluating it.
+fn required(statement : &Statement) -> HashSet<Variable> {
+    return match statement {
+        Statement::Variable(x) if !x.is_anonymous() => HashSet::from([*x]),
+        Statement::BinaryOperator(BinaryOperator::Index(collection, _)) => required(collection),
+        x => x.children().into_iter().flat_map(required).collect(),
+    };
+}
+
+// the variables in arithmetic (or comparison) expressions and function calls contained in `statement`.
+fn arithmetic(statement : &Statement) -> HashSet<Variable> {
+    return match statement {
+        Statement::BinaryOperator(BinaryOperator::Index(_, _)) => HashSet::new(),
+        Statement::BinaryOperator(_) | Statement::UnaryOperator(_) | Statement::Call(_) => required(statement),
+        x => x.children().into_iter().flat_map(arithmetic).collect(),
+    };
+}
+
+// whether matching `statement` with a value binds its variables, an arithmetic expression is only solved if it is linear in a 
+// single variable (see `invert`).
+fn solvable(statement : &Statement) -> bool {
+    return match statement {
+        Statement::BinaryOperator(BinaryOperator::Index(_, _)) => true,
+        Statement::BinaryOperator(_) | Statement::UnaryOperator(_) => match variables(&[statement]).len() {
+            0 => true,
+            1 => non_invertible(statement).is_none(),
+            _ => false,
+        },
+        Statement::Call(_) => variables(&[statement]).is_empty(),
+        x => x.children().into_iter().all(solvable),
+    };
+}
+
+fn variables(statements : &[&Statement]) -> HashSet<Variable> {
+    let mut counts = HashMap::new();
+    count(statements, &mut counts);
+    return counts.into_keys().collect();
+}
+
+// counts the occurrences of each (named) variable in `statements`.
+fn count(statements : &[&Statement], counts : &mut HashMap<Variable, usize>) {
+    let mut stack : Vec<&Statement> = statements.to_vec();
+    while let Some(x) = stack.pop() {
+        match x {
+            Statement::Variable(x) if !x.is_anonymous() => *counts.entry(*x).or_insert(0) += 1,
+            x => stack.extend(x.children()),
+        }
+    }
+}
+
+fn statements<'a>(conditions : &'a [Condition], actions : &'a [Action]) -> Vec<&'a Statement> {
+    return conditions.iter().map(|x| &x.0).chain(actions.iter().map(|x| &x.0)).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpret::Program;
+    use crate::registry::Registry;
+    use crate::error::TeleoraError;
+
+    #[test]
+    fn unbound_at_its_condition() {
+        let source = "g(X) : X > 1, Y > 2 -> yes";
+        let (_, errors) = Program::parse_recovering(source, Registry::default());
+        let [TeleoraError::UnboundVariable { span, variable, .. }] = errors.as_slice() else { panic!("{:?}", errors) };
+        assert_eq!(variable, "Y");
+        assert_eq!(&source[span.start..span.end], "Y > 2");
+        // an action is reported where it is written
+        let source = "g(X) : X > 1 -> a(Y)";
+        let (_, errors) = Program::parse_recovering(source, Registry::default());
+        let [TeleoraError::UnboundVariable { span, .. }] = errors.as_slice() else { panic!("{:?}", errors) };
+        assert_eq!(&source[span.start..span.end], "a(Y)");
+    }
+
+    #[test]
+    fn singleton_at_the_variable() {
+        let source = "g(X, Y) : X > 1 -> yes";
+        let (program, errors) = Program::parse_recovering(source, Registry::default());
+        assert!(errors.is_empty(), "{:?}", errors);
+        let [warning] = program.warnings() else { panic!("{:?}", program.warnings()) };
+        let span = warning.span.as_ref().unwrap();
+        assert_eq!((warning.code, &source[span.start..span.end]), ("W0001", "Y"));
+    }
+
+    #[test]
+    fn unbound_is_not_also_a_singleton() {
+        let (program, errors) = Program::parse_recovering("g(X) : X > 1, Y > 2 -> yes\nh(X) : X > 1 -> yes", Registry::default());
+        assert_eq!(errors.len(), 1);
+        assert!(program.warnings().is_empty(), "{:?}", program.warnings());
+    }
+}
@@ -0,0 +1,239 @@
+use std::fmt;
+use crate::error::{TeleoraError, Span, listing};
+
+/// How serious a diagnostic is, an error prevents the program from running while a warning does not.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem with a program that is ready to be shown to the person who wrote it, in the style of a compiler: a headline
+/// with a stable code, the offending source line with a caret under the problem, and notes and help that explain it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity : Severity,
+    pub code : &'static str,        // e.g. `E0001`, see `TeleoraError::code`
+    pub message : String,           // the headline, it does not repeat the location
+    pub span : Option<Span>,        // where the problem is, if it is in the source
+    pub label : Option<String>,     // written after the caret
+    pub notes : Vec<String>,        // more about what went wrong
+    pub help : Vec<String>,         // how it might be fixed
+}
+
+impl Diagnostic {
+    pub fn error(code : &'static str, message : String, span : Option<Span>) -> Self {
+        return Diagnostic { severity : Severity::Error, code : code, message : message, span : span, label : None, notes : Vec::new(), help : Vec::new() };
+    }
+
+    pub fn warning(code : &'static str, message : String, span : Option<Span>) -> Self {
+        return Diagnostic { severity : Severity::Warning, ..Diagnostic::error(code, message, span) };
+    }
+
+    pub fn with_label(mut self, label : String) -> Self {
+        self.label = Some(label);
+        return self;
+    }
+
+    pub fn with_note(mut self, note : String) -> Self {
+        self.notes.push(note);
+        return self;
+    }
+
+    pub fn with_help(mut self, help : String) -> Self {
+        self.help.push(help);
+        return self;
+    }
+
+    /// names the source that the problem is in, see `TeleoraError::in_file`.
+    pub fn in_file(mut self, file : &str) -> Self {
+        self.span = self.span.map(|x| x.in_file(file));
+        return self;
+    }
+
+    /// writes this diagnostic with the line of `source` that it refers to, `colour` uses ANSI escape codes for a terminal.
+    pub fn render(&self, source : &str, colour : bool) -> String {
+        let paint = |style : &str, text : &str| if colour { format!("\x1b[{}m{}\x1b[0m", style, text) } else { text.to_string() };
+        let (severity, style) = match self.severity {
+            Severity::Error => ("error", "1;31"),       // bold red
+            Severity::Warning => ("warning", "1;33"),   // bold yellow
+        };
+        let blue = "1;34";
+        let mut lines = vec![format!("{}{}", paint(style, &format!("{}[{}]", severity, self.code)), paint("1", &format!(": {}", self.message)))];
+        // the gutter is as wide as the line number
+        let mut gutter = String::new();
+        if let Some(span) = &self.span {
+            gutter = " ".repeat(span.line.to_string().len());
+            let file = span.file.as_deref().unwrap_or("<source>");
+            lines.push(format!("{}{} {}:{}:{}", gutter, paint(blue, "-->"), file, span.line, span.column));
+            if let Some(text) = source.lines().nth(span.line.saturating_sub(1)) {
+                // the caret is aligned by repeating any tabs before it, it spans the text on this line
+                let before : String = text.chars().take(span.column.saturating_sub(1)).map(|x| if x == '\t' { '\t' } else { ' ' }).collect();
+                let rest = text.chars().count().saturating_sub(span.column.saturating_sub(1));
+                let width = source.get(span.start..span.end).map_or(0, |x| x.lines().next().unwrap_or("").chars().count()).min(rest).max(1);
+                let label = self.label.as_ref().map_or(String::new(), |x| format!(" {}", x));
+                lines.push(format!("{} {}", gutter, paint(blue, "|")));
+                lines.push(format!("{} {} {}", paint(blue, &span.line.to_string()), paint(blue, "|"), text));
+                lines.push(format!("{} {} {}{}", gutter, paint(blue, "|"), before, paint(style, &format!("{}{}", "^".repeat(width), label))));
+            }
+        }
+        for note in self.notes.iter() {
+            lines.push(format!("{} {} {}: {}", gutter, paint(blue, "="), paint("1", "note"), note));
+        }
+        for help in self.help.iter() {
+            lines.push(format!("{} {} {}: {}", gutter, paint(blue, "="), paint("1", "help"), help));
+        }
+        return lines.join("\n");
+    }
+}
+
+// a one line description, e.g. for a log.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.span {
+            Some(span) => write!(f, "{} ({})", self.message, span),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl From<&TeleoraError> for Diagnostic {
+    fn from(error : &TeleoraError) -> Self {
+        let diagnostic = |message : String| Diagnostic::error(error.code(), message, error.span().cloned());
+        return match error {
+            TeleoraError::Syntax { expected, unexpected, .. } => {
+                let mut result = match expected.split_first() {
+                    Some((first, rest)) => {
+                        let result = diagnostic(format!("expected {}{}", first, context(first))).with_label(format!("expected {}", first));
+                        if rest.is_empty() { result } else { result.with_note(format!("{} would also be accepted here", or(rest))) }
+                    },
+                    None => diagnostic("syntax error".to_string()),
+                };
+                if !unexpected.is_empty() {
+                    result = result.with_note(format!("{} cannot appear here", or(unexpected)));
+                }
+                match expected.first().map(String::as_str) {
+                    Some("`->`") => result.with_help("a rule is written as its conditions, then `->` and its action, e.g. `g(X) : X > 1 -> a(X)`".to_string()),
+                    Some("`:`") => result.with_help("a goal is written as its head, then `:` and its rules, e.g. `g(X) : X > 1 -> a(X)`".to_string()),
+                    Some("a name") => result.with_help("goals, atoms and functions are named with a lowercase letter first, variables with an uppercase letter or `_`".to_string()),
+                    _ => result,
+                }
+            },
+            TeleoraError::InvalidLiteral { literal, reason, .. } => diagnostic(format!("invalid literal `{}`", literal)).with_label(reason.clone()),
+            TeleoraError::UnknownFunction { name, known, .. } => {
+                let result = diagnostic(format!("unknown function `{}`", name)).with_label("not a registered function".to_string())
+                    .with_note(format!("the registered functions are: {}", listing(known)));
+                match closest(name, known) {
+                    Some(x) => result.with_help(format!("did you mean `{}`?", x)),
+                    None => result.with_help("functions are registered by the host with `Registry::function`".to_string()),
+                }
+            },
+            TeleoraError::UnknownOperator { name, known, .. } => {
+                let result = diagnostic(format!("unknown operator `{}`", name)).with_label("not a registered operator".to_string())
+                    .with_note(format!("the registered operators are: {}", listing(known)));
+                match closest(name, known) {
+                    Some(x) => result.with_help(format!("did you mean `{}`?", x)),
+                    None => result.with_help("operators are registered by the host with `Registry::operator`".to_string()),
+                }
+            },
+            TeleoraError::WrongArity { name, expected, found, .. } => diagnostic(format!("function `{}` takes {} argument(s) but is given {}", name, expected, found))
+                .with_label(format!("given {} argument(s)", found)),
+            TeleoraError::UnboundVariable { goal, variable, statement, .. } => diagnostic(format!("`{}` is never bound in goal `{}`", variable, goal))
+                .with_label(format!("`{}` is used in `{}`", variable, statement))
+                .with_help(format!("`{}` should appear in the head or on one side of an `=` condition whose other side is bound", variable)),
+            TeleoraError::InvalidHead { goal, argument, .. } => diagnostic(format!("goal `{}` cannot be matched with argument `{}`", goal, argument))
+                .with_label("cannot be solved while matching".to_string())
+                .with_help("arithmetic in a goal head may only combine a single variable with numbers using `+`, `-`, `*` or negation (e.g. `N + 1`)".to_string()),
+            TeleoraError::SharedCapture { goal, collection, .. } => diagnostic(format!("goal `{}` captures the remaining items of `{}` more than once", goal, collection))
+                .with_help("an unordered collection may capture its remaining items at most once (e.g. `{X, *REST}`)".to_string()),
+            TeleoraError::Unexpected { expected, found, .. } => diagnostic(format!("expected {} but found {}", expected, found)),
+            TeleoraError::NoAction => diagnostic(error.to_string()).with_help("end the goal with a rule that always applies, e.g. `g(_) : -> idle`".to_string()),
+            TeleoraError::Evaluation { message, trace, .. } => trace.iter()
+                .fold(diagnostic(message.clone()).with_label("while evaluating this".to_string()), |result, x| result.with_note(format!("in a goal called from {}", x))),
+            TeleoraError::ModuleNotFound { module, searched, .. } => diagnostic(format!("cannot find module `{}`", module)).with_label("imported here".to_string())
+                .with_note(format!("looked for {}", searched.join(", ")))
+                .with_help("modules are found relative to the importing file, then in each directory of the search path (see `Loader::search`)".to_string()),
+            TeleoraError::ImportCycle { cycle, .. } => diagnostic(format!("module `{}` imports itself", cycle.first().map_or("", String::as_str)))
+                .with_label("this import completes the cycle".to_string())
+                .with_note(cycle.join(" imports ")),
+            TeleoraError::Conflict { name, other, .. } => diagnostic(format!("`{}` is defined more than once", name)).with_label("defined again here".to_string())
+                .with_note(format!("`{}` is first defined at {}", name, other))
+                .with_help("import only the goals that are needed (e.g. `import nav::{move}`) or use the qualified name (e.g. `nav::move`)".to_string()),
+            TeleoraError::NotExported { module, name, exported, .. } => {
+                let result = diagnostic(format!("module `{}` does not export `{}`", module, name)).with_label("not exported".to_string())
+                    .with_note(format!("`{}` exports: {}", module, listing(exported)));
+                match closest(name, exported) {
+                    Some(x) => result.with_help(format!("did you mean `{}`?", x)),
+                    None => result.with_help(format!("a module makes its goals visible to others with an export list, e.g. `export {{{}}}`", name)),
+                }
+            },
+            TeleoraError::UnknownModule { module, imported, .. } => {
+                let result = diagnostic(format!("unknown module `{}`", module)).with_label("not imported".to_string())
+                    .with_note(format!("the imported modules are: {}", listing(imported)));
+                match closest(module, imported) {
+                    Some(x) => result.with_help(format!("did you mean `{}`?", x)),
+                    None => result.with_help(format!("import the module at the top of the file, e.g. `import {}`", module)),
+                }
+            },
+            TeleoraError::FormatChanged => diagnostic("formatting would change the meaning of the program".to_string())
+                .with_note("the program is left as it is, this is a bug in the formatter".to_string()),
+            TeleoraError::InvalidName { .. } | TeleoraError::UnknownGoal { .. } | TeleoraError::NoGoals => diagnostic(error.to_string()),
+        };
+    }
+}
+
+// what a missing token usually follows or closes.
+fn context(token : &str) -> &'static str {
+    return match token {
+        "`->`" => " after the conditions",
+        "`:`" => " after the head of the goal",
+        "`)`" => " to close `(`",
+        "`]`" => " to close `[`",
+        "`}`" => " to close `{`",
+        _ => "",
+    };
+}
+
+// e.g. "`,`, `;` or an operator".
+fn or(terms : &[String]) -> String {
+    return match terms.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => String::new(),
+    };
+}
+
+// the name most like `name` (at most two edits away), a likely typo.
+fn closest<'a, T : AsRef<str>>(name : &str, names : &'a [T]) -> Option<&'a str> {
+    return names.iter().map(|x| (distance(name, x.as_ref()), x.as_ref())).filter(|(d, _)| *d <= 2).min().map(|(_, x)| x);
+}
+
+// the number of characters that must be inserted, removed or replaced to turn one name into the other.
+fn distance(x : &str, y : &str) -> usize {
+    let y : Vec<char> = y.chars().collect();
+    let mut previous : Vec<usize> = (0..=y.len()).collect();
+    for (i, a) in x.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in y.iter().enumerate() {
+            current.push((previous[j] + usize::from(a != *b)).min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    return previous[y.len()];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_the_known_names() {
+        let error = TeleoraError::UnknownFunction { span : Span::default(), name : "lne".to_string(), known : vec!["concat".to_string(), "len".to_string()] };
+        let diagnostic = Diagnostic::from(&error);
+        assert_eq!(diagnostic.notes, ["the registered functions are: `concat`, `len`"]);
+        assert_eq!(diagnostic.help, ["did you mean `len`?"]);
+        assert!(error.to_string().ends_with("the registered functions are: `concat`, `len`."));
+        let error = TeleoraError::UnknownOperator { span : Span::default(), name : "dot".to_string(), known : Vec::new() };
+        assert_eq!(Diagnostic::from(&error).notes, ["the registered operators are: none"]);
+    }
+}
@@ -0,0 +1,329 @@
+use std::fmt;
+use std::sync::Arc;
+use itertools::Itertools;
+use pest::error::{Error, ErrorVariant, InputLocation, LineColLocation};
+use pest::Position;
+use crate::Rule;
+use crate::statement::EvaluationError;
+
+/// Where something is written in the source of a program or an observation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub file : Option<Arc<str>>,    // the name of the source (e.g. its path), if it was given one
+    pub start : usize,              // the byte offsets of the text in the source
+    pub end : usize,
+    pub line : usize,               // the position of the start of the text, counted from 1
+    pub column : usize,
+}
+
+/// An error that prevents a program or an observation from being loaded, or a goal from producing an action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TeleoraError {
+    Syntax { span : Span, expected : Vec<String>, unexpected : Vec<String> },   // the source does not follow the grammar
+    InvalidLiteral { span : Span, literal : String, reason : String },         // e.g. an integer that is too large
+    UnknownFunction { span : Span, name : String, known : Vec<String> },        // a call of a function that is not registered
+    UnknownOperator { span : Span, name : String, known : Vec<String> },        // an infix operator that is not registered
+    WrongArity { span : Span, name : String, expected : usize, found : usize }, // a function given the wrong number of arguments
+    UnboundVariable { span : Span, goal : String, variable : String, statement : String }, // a variable that can never be bound
+    InvalidHead { span : Span, goal : String, argument : String },             // a head argument that cannot be matched
+    SharedCapture { span : Span, goal : String, collection : String },         // an unordered collection with several captures
+    Unexpected { span : Span, expected : String, found : String },             // parsed pairs of the wrong kind were given
+    InvalidName { name : String, reserved : Vec<&'static str> },               // a function or operator that cannot be registered
+    UnknownGoal { name : String },                                              // there is no goal with this name
+    NoGoals,                                                                    // the program has no goal to evaluate
+    NoAction,                                                                   // no rule of the goal produced an action
+    Evaluation { error : Box<EvaluationError>, message : String, span : Option<Span>, trace : Vec<Span> }, // see `Failure`
+    ModuleNotFound { span : Span, module : String, searched : Vec<String> },    // an import of a file that cannot be read
+    ImportCycle { span : Span, cycle : Vec<String> },                           // a module that (indirectly) imports itself
+    Conflict { span : Span, name : String, other : Span },                      // a name that is defined twice
+    NotExported { span : Span, module : String, name : String, exported : Vec<String> }, // a goal that a module does not export
+    UnknownModule { span : Span, module : String, imported : Vec<String> },     // a qualified name of a module that is not imported
+    FormatChanged,                                                              // formatting a program changed its meaning
+}
+
+/// An error that stopped the evaluation of a goal, and where it happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub error : Box<EvaluationError>,
+    pub span : Option<Span>,    // the head argument, condition or action that was being evaluated
+    pub trace : Vec<Span>,      // the actions that called the goal that it happened in (and so on), innermost first
+}
+
+impl Failure {
+    /// records that the failure happened while evaluating what is written at `span`, unless it is already known where.
+    pub fn locate(&mut self, span : &Span) {
+        if self.span.is_none() {
+            self.span = Some(span.clone());
+        }
+    }
+
+    /// records that the goal that failed was called by the action written at `span`.
+    pub fn called_from(mut self, span : &Span) -> Self {
+        match self.span {
+            Some(_) => self.trace.push(span.clone()),
+            None => self.span = Some(span.clone()),
+        }
+        return self;
+    }
+}
+
+impl From<EvaluationError> for Failure {
+    fn from(error : EvaluationError) -> Self {
+        return Failure { error : Box::new(error), span : None, trace : Vec::new() };
+    }
+}
+
+impl Span {
+    /// names the source that this span is in, unless it is already named (e.g. it is in a module that was imported).
+    pub fn in_file(mut self, file : &str) -> Self {
+        if self.file.is_none() {
+            self.file = Some(Arc::from(file));
+        }
+        return self;
+    }
+
+    /// a span of a part of the source that was parsed on its own, as a span of the whole source given where the part begins.
+    pub fn within(mut self, origin : &Span) -> Self {
+        if self.line == 1 {
+            self.column += origin.column - 1;
+        }
+        self.line += origin.line - 1;
+        self.start += origin.start;
+        self.end += origin.start;
+        return self;
+    }
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span : pest::Span<'_>) -> Self {
+        let (line, column) = span.start_pos().line_col();
+        return Span { file : None, start : span.start(), end : span.end(), line : line, column : column };
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}, line {}, column {}", file, self.line, self.column),
+            None => write!(f, "line {}, column {}", self.line, self.column),
+        }
+    }
+}
+
+impl TeleoraError {
+    /// where the problem is in the source, if it is known.
+    pub fn span(&self) -> Option<&Span> {
+        return match self {
+            TeleoraError::Syntax { span, .. } | TeleoraError::InvalidLiteral { span, .. } | TeleoraError::UnknownFunction { span, .. }
+                | TeleoraError::UnknownOperator { span, .. } | TeleoraError::WrongArity { span, .. } | TeleoraError::UnboundVariable { span, .. }
+                | TeleoraError::InvalidHead { span, .. } | TeleoraError::SharedCapture { span, .. } | TeleoraError::Unexpected { span, .. }
+                | TeleoraError::ModuleNotFound { span, .. } | TeleoraError::ImportCycle { span, .. } | TeleoraError::Conflict { span, .. }
+                | TeleoraError::NotExported { span, .. } | TeleoraError::UnknownModule { span, .. } => Some(span),
+            TeleoraError::Evaluation { span, .. } => span.as_ref(),
+            TeleoraError::InvalidName { .. } | TeleoraError::UnknownGoal { .. } | TeleoraError::NoGoals | TeleoraError::NoAction
+                | TeleoraError::FormatChanged => None,
+        };
+    }
+
+    /// names the source that the problem is in, e.g. the path of the file that the program was read from.
+    pub fn in_file(mut self, file : &str) -> Self {
+        if let Some(span) = self.span_mut() {
+            *span = std::mem::take(span).in_file(file);
+        }
+        if let TeleoraError::Evaluation { trace, .. } = &mut self {
+            *trace = std::mem::take(trace).into_iter().map(|x| x.in_file(file)).collect();
+        }
+        return self;
+    }
+
+    /// see `Span::within`.
+    pub fn within(mut self, origin : &Span) -> Self {
+        if let Some(span) = self.span_mut() {
+            *span = std::mem::take(span).within(origin);
+        }
+        return self;
+    }
+
+    fn span_mut(&mut self) -> Option<&mut Span> {
+        return match self {
+            TeleoraError::Syntax { span, .. } | TeleoraError::InvalidLiteral { span, .. } | TeleoraError::UnknownFunction { span, .. }
+                | TeleoraError::UnknownOperator { span, .. } | TeleoraError::WrongArity { span, .. } | TeleoraError::UnboundVariable { span, .. }
+                | TeleoraError::InvalidHead { span, .. } | TeleoraError::SharedCapture { span, .. } | TeleoraError::Unexpected { span, .. }
+                | TeleoraError::ModuleNotFound { span, .. } | TeleoraError::ImportCycle { span, .. } | TeleoraError::Conflict { span, .. }
+                | TeleoraError::NotExported { span, .. } | TeleoraError::UnknownModule { span, .. } => Some(span),
+            TeleoraError::Evaluation { span, .. } => span.as_mut(),
+            TeleoraError::InvalidName { .. } | TeleoraError::UnknownGoal { .. } | TeleoraError::NoGoals | TeleoraError::NoAction
+                | TeleoraError::FormatChanged => None,
+        };
+    }
+
+    /// a stable identifier for the kind of problem (e.g. `E0001` for a syntax error), it does not change between versions.
+    pub fn code(&self) -> &'static str {
+        return match self {
+            TeleoraError::Syntax { .. }             => "E0001",
+            TeleoraError::InvalidLiteral { .. }     => "E0002",
+            TeleoraError::UnknownFunction { .. }    => "E0003",
+            TeleoraError::UnknownOperator { .. }    => "E0004",
+            TeleoraError::WrongArity { .. }         => "E0005",
+            TeleoraError::UnboundVariable { .. }    => "E0006",
+            TeleoraError::InvalidHead { .. }        => "E0007",
+            TeleoraError::SharedCapture { .. }      => "E0008",
+            TeleoraError::Unexpected { .. }         => "E0009",
+            TeleoraError::InvalidName { .. }        => "E0010",
+            TeleoraError::UnknownGoal { .. }        => "E0011",
+            TeleoraError::NoGoals                   => "E0012",
+            TeleoraError::NoAction                  => "E0013",
+            TeleoraError::Evaluation { .. }         => "E0014",
+            TeleoraError::ModuleNotFound { .. }     => "E0015",
+            TeleoraError::ImportCycle { .. }        => "E0016",
+            TeleoraError::Conflict { .. }           => "E0017",
+            TeleoraError::NotExported { .. }        => "E0018",
+            TeleoraError::UnknownModule { .. }      => "E0019",
+            TeleoraError::FormatChanged             => "E0020",
+        };
+    }
+
+    /// a syntax error in `source`, if pest tracked its parse attempts (see `pest::set_error_detail`) the error is placed where
+    /// parsing got furthest and describes the tokens that could appear there (e.g. `->`), otherwise the rules that could.
+    pub fn syntax(error : Error<Rule>, source : &str) -> Self {
+        let Some(attempts) = error.parse_attempts() else {
+            return TeleoraError::from(error);
+        };
+        let Some(position) = Position::new(source, attempts.max_position) else {
+            return TeleoraError::from(error);
+        };
+        let (line, column) = position.line_col();
+        let span = Span { file : None, start : position.pos(), end : position.pos(), line : line, column : column };
+        return TeleoraError::Syntax { span : span, expected : expected(attempts.expected_tokens()), unexpected : expected(attempts.unexpected_tokens()) };
+    }
+}
+
+// the rules that pest expected (or did not expect) are described by their token (e.g. `>=`) or what they stand for.
+impl From<Error<Rule>> for TeleoraError {
+    fn from(error : Error<Rule>) -> Self {
+        let (start, end) = match error.location {
+            InputLocation::Pos(x) => (x, x),
+            InputLocation::Span(x) => x,
+        };
+        let (line, column) = match error.line_col {
+            LineColLocation::Pos(x) | LineColLocation::Span(x, _) => x,
+        };
+        let span = Span { file : None, start : start, end : end, line : line, column : column };
+        let describe = |rules : Vec<Rule>| rules.into_iter().map(describe).unique().collect();
+        return match error.variant {
+            ErrorVariant::ParsingError { positives, negatives } => TeleoraError::Syntax { span : span, expected : describe(positives), unexpected : describe(negatives) },
+            ErrorVariant::CustomError { message } => TeleoraError::Syntax { span : span, expected : Vec::new(), unexpected : vec![message] },
+        };
+    }
+}
+
+impl fmt::Display for TeleoraError {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TeleoraError::Syntax { span, expected, unexpected } => {
+                write!(f, "Syntax error ({})", span)?;
+                if !expected.is_empty() {
+                    write!(f, ", expected {}", expected.iter().join(" or "))?;
+                }
+                if !unexpected.is_empty() {
+                    write!(f, ", unexpected {}", unexpected.iter().join(" or "))?;
+                }
+                write!(f, ".")
+            },
+            TeleoraError::InvalidLiteral { span, literal, reason } => write!(f, "Invalid literal `{}` ({}), {}.", literal, span, reason),
+            TeleoraError::UnknownFunction { span, name, known } => write!(f, "Unknown function `{}` ({}), the registered functions are: {}.", name, span, listing(known)),
+            TeleoraError::UnknownOperator { span, name, known } => write!(f, "Unknown operator `{}` ({}), the registered operators are: {}.", name, span, listing(known)),
+            TeleoraError::WrongArity { span, name, expected, found } => write!(f, "Function `{}` ({}) takes {} argument(s) but is given {}.", name, span, expected, found),
+            TeleoraError::UnboundVariable { span, goal, variable, statement } => write!(f, "Goal `{}` ({}) uses `{}` in `{}` but `{}` is never bound, it should appear in the head or on one side of an `=` condition whose other side is bound.", goal, span, variable, statement, variable),
+            TeleoraError::InvalidHead { span, goal, argument } => write!(f, "Goal `{}` ({}) cannot be matched with argument `{}`, arithmetic in a goal head may only combine a single variable with numbers using `+`, `-`, `*` or negation (e.g. `N + 1`).", goal, span, argument),
+            TeleoraError::SharedCapture { span, goal, collection } => write!(f, "Goal `{}` ({}) contains `{}`, an unordered collection may capture its remaining items at most once (e.g. `{{X, *REST}}`).", goal, span, collection),
+            TeleoraError::Unexpected { span, expected, found } => write!(f, "Expected {} ({}) but found {}.", expected, span, found),
+            TeleoraError::InvalidName { name, reserved } => write!(f, "Cannot register `{}`, names must begin with a lowercase letter followed by letters, digits or `_` (and cannot be one of {}).", name, reserved.join(", ")),
+            TeleoraError::UnknownGoal { name } => write!(f, "Unknown goal `{}`, the program has no goal with this name.", name),
+            TeleoraError::NoGoals => write!(f, "The program has no goals to evaluate."),
+            TeleoraError::NoAction => write!(f, "Failed to obtain an action, did you forget to use the default rule?"),
+            TeleoraError::Evaluation { message, span : Some(span), .. } => write!(f, "{} ({})", message, span),
+            TeleoraError::Evaluation { message, span : None, .. } => write!(f, "{}", message),
+            TeleoraError::ModuleNotFound { span, module, searched } => write!(f, "Cannot find module `{}` ({}), looked for {}.", module, span, searched.join(", ")),
+            TeleoraError::ImportCycle { span, cycle } => write!(f, "Import cycle ({}), {}.", span, cycle.join(" imports ")),
+            TeleoraError::Conflict { span, name, other } => write!(f, "`{}` ({}) is already defined ({}).", name, span, other),
+            TeleoraError::NotExported { span, module, name, exported } => write!(f, "Module `{}` does not export `{}` ({}), it exports: {}.", module, name, span, listing(exported)),
+            TeleoraError::FormatChanged => write!(f, "Formatting would change the meaning of the program, this is a bug in the formatter."),
+            TeleoraError::UnknownModule { span, module, imported } => write!(f, "Unknown module `{}` ({}), the imported modules are: {}.", module, span, listing(imported)),
+        }
+    }
+}
+
+impl std::error::Error for TeleoraError {}
+
+// tokens that separate other tokens, they are never what is missing.
+const LAYOUT : [&str; 8] = [" ", "\t", "\n", "\r", "\r\n", "//", "///", "/*"];
+// tokens that end or separate parts of a goal, these are usually what is missing so they are listed first.
+const PUNCTUATION : [&str; 8] = ["->", ":", ",", ";", ")", "]", "}", "|"];
+// tokens that continue an expression, some of them (e.g. `-` or `[`) may also begin one.
+const OPERATORS : [&str; 18] = ["!=", "%", "&&", "*", "+", "++", "-", ".", "/", "<", "=", ">", "[", "||", "in", "intersect", "minus", "union"];
+// tokens that begin an expression.
+const STARTS : [&str; 15] = ["(", "[", "[]", "{", "{}", "\"", "!", "-", "true", "false", "0x", "_", "a..z", "A..Z", "0..9"];
+
+// describes the tokens that pest tried where parsing failed, e.g. "`->`" or "an operator". Tokens that could continue a name
+// (e.g. the letters after `X`) are only described if nothing else could appear.
+fn expected<T : fmt::Display>(tokens : Vec<T>) -> Vec<String> {
+    // tokens are written as they appear in the grammar, ranges of characters as `a..z`
+    let tokens : Vec<String> = tokens.into_iter()
+        .map(|x| x.to_string())
+        .filter(|x| !LAYOUT.contains(&x.as_str()) && x != "BUILTIN_RULE")
+        .collect();
+    let has = |set : &[&str]| tokens.iter().any(|x| set.contains(&x.as_str()));
+    // a number may only begin an expression (the digits of a name are a range that is shared with other tokens)
+    let expression = has(&["0x"]);
+    let mut terms : Vec<String> = PUNCTUATION.iter().chain(if expression { [].iter() } else { ["(", "{"].iter() })
+        .filter(|x| has(&[x]))
+        .map(|x| format!("`{}`", x))
+        .collect();
+    if expression {
+        terms.push("an expression".to_string());
+    }
+    if tokens.iter().any(|x| OPERATORS.contains(&x.as_str()) && !(expression && STARTS.contains(&x.as_str()))) {
+        terms.push("an operator".to_string());
+    }
+    if terms.is_empty() && has(&["a..z"]) {
+        terms.push("a name".to_string());
+    }
+    if terms.is_empty() && has(&["A..Z", "_"]) {
+        terms.push("a variable".to_string());
+    }
+    if terms.is_empty() {
+        terms.extend(tokens.iter().map(|x| format!("`{}`", x)));
+    }
+    return terms;
+}
+
+// how a rule of the grammar is named in an error message.
+fn describe(rule : Rule) -> String {
+    let token = match rule {
+        Rule::gt => ">", Rule::lt => "<", Rule::eq => "=", Rule::neq => "!=", Rule::gte => ">=", Rule::lte => "<=",
+        Rule::and => "&&", Rule::or => "||", Rule::add => "+", Rule::subtract | Rule::minus => "-", Rule::multiply => "*",
+        Rule::divide => "/", Rule::modulo => "%", Rule::concat => "++", Rule::not => "!", Rule::pipe => "|", Rule::index => "[",
+        Rule::field => ".", Rule::member => "in", Rule::union => "union", Rule::intersect => "intersect", Rule::difference => "minus",
+        Rule::EOI | Rule::SILENT_EOI => return "end of input".to_string(),
+        Rule::infix => return "an operator".to_string(),
+        Rule::expr | Rule::lexpr => return "an expression".to_string(),
+        Rule::signed_integer | Rule::integer | Rule::signed_float | Rule::float => return "a number".to_string(),
+        Rule::variable => return "a variable".to_string(),
+        Rule::atom => return "an atom".to_string(),
+        Rule::string => return "a string".to_string(),
+        Rule::boolean => return "`true` or `false`".to_string(),
+        Rule::compound => return "a goal or an action".to_string(),
+        Rule::goal => return "a goal".to_string(),
+        rule => return format!("{:?}", rule),
+    };
+    return format!("`{}`", token);
+}
+
+// a list of names for an error message, e.g. "`len`, `concat`".
+pub(crate) fn listing<T : AsRef<str>>(names : &[T]) -> String {
+    if names.is_empty() {
+        return "none".to_string();
+    }
+    return names.iter().map(|x| format!("`{}`", x.as_ref())).join(", ");
+}
@@ -0,0 +1,498 @@
+use std::path::Path;
+use pest::Parser;
+use pest::iterators::Pair;
+use crate::{Rule as AstRule, TeleoraParser};
+use crate::error::TeleoraError;
+use crate::interpret::skipped;
+use crate::registry::Registry;
+use crate::module::Loader;
+
+// The canonical layout of a program: one import, export or short goal per line, `:` and `->` with a space on each side,
+// binary operators too, and items separated by `, `. Alternative conditions are separated by `; `, a `||` between them is
+// rewritten as `;` since they mean the same (a disjunction within a condition is parenthesised, so it is kept). A long goal has one rule per line inside its braces, the arrows of its
+// rules are aligned. Blank lines between goals are kept (at most one) and so are comments, those that follow a rule on its
+// line stay there and the others are written on their own line before what follows them. The formatter works on the parsed
+// pairs and finds comments in the text between them, since comments are not part of the parsed pairs.
+
+const INDENT : &str = "    ";
+
+/// writes the program in `source` in the canonical layout. Loading the result gives a program equal to the one in `source`,
+/// otherwise the program is not formatted and `TeleoraError::FormatChanged` is returned. Both are loaded as the file at
+/// `path` (so that its imports are found) with the functions and operators of `registry`.
+pub fn format(source : &str, path : Option<&Path>, registry : Registry) -> Result<String, TeleoraError> {
+    pest::set_error_detail(true); // so that syntax errors can describe the tokens that were expected
+    let document = TeleoraParser::parse(AstRule::document, source).map_err(|e| TeleoraError::syntax(e, source))?.next().unwrap();
+    // text that could not be parsed cannot be formatted
+    let invalid = document.clone().into_inner()
+        .flat_map(|x| std::iter::once(x.clone()).chain(x.into_inner()))
+        .find(|x| matches!(x.as_rule(), AstRule::invalid | AstRule::invalid_body));
+    if let Some(pair) = invalid {
+        let rule = if pair.as_rule() == AstRule::invalid { AstRule::goal_only } else { AstRule::body_only };
+        return Err(skipped(pair, rule, &registry));
+    }
+    let formatted = Printer { source : source, position : 0, newlines : 0 }.document(document);
+    // the formatted program must be the same program, with the same problems (e.g. an unknown function)
+    let (program, errors) = Loader::default().load_source(source, path, registry.clone());
+    let (reformatted, reerrors) = Loader::default().load_source(&formatted, path, registry);
+    let codes = |errors : &[TeleoraError]| errors.iter().map(TeleoraError::code).collect::<Vec<_>>();
+    if program != reformatted || codes(&errors) != codes(&reerrors) {
+        return Err(TeleoraError::FormatChanged);
+    }
+    return Ok(formatted);
+}
+
+// a comment found between the parsed pairs.
+struct Comment<'s> {
+    text : &'s str,
+    own_line : bool,        // it is not on the line of what comes before it
+    blank_before : bool,    // there is a blank line before it
+}
+
+// writes parsed pairs in the canonical layout, in the order they appear in the source.
+struct Printer<'s> {
+    source : &'s str,
+    position : usize,   // the end of the source that has been written
+    newlines : usize,   // the line breaks after the last comment found by `trivia`
+}
+
+impl<'s> Printer<'s> {
+    fn document(&mut self, pair : Pair<'s, AstRule>) -> String {
+        let mut lines : Vec<String> = Vec::new();
+        for item in pair.into_inner() {
+            for comment in self.trivia(start(&item)) {
+                match lines.last_mut() {
+                    Some(line) if !comment.own_line => { line.push(' '); line.push_str(comment.text); },
+                    _ => {
+                        if comment.blank_before && !lines.is_empty() {
+                            lines.push(String::new());
+                        }
+                        lines.push(comment.text.to_string());
+                    },
+                }
+            }
+            if item.as_rule() == AstRule::EOI {
+                break;
+            }
+            if self.newlines > 1 && !lines.is_empty() {
+                lines.push(String::new());
+            }
+            match item.as_rule() {
+                AstRule::import | AstRule::export => lines.push(self.header(item)),
+                _ => lines.extend(self.goal(item)),
+            }
+        }
+        if lines.is_empty() {
+            return String::new();
+        }
+        return lines.join("\n") + "\n";
+    }
+
+    // e.g. `import nav::{move}` or `export {move, stop}`.
+    fn header(&mut self, pair : Pair<'s, AstRule>) -> String {
+        let rule = pair.as_rule();
+        let mut inner = pair.into_inner();
+        if rule == AstRule::export {
+            return format!("export {}", self.names(inner.next().unwrap()));
+        }
+        let module = self.leaf(&inner.next().unwrap());
+        return match inner.next() {
+            Some(names) => format!("import {}::{}", module, self.names(names)),
+            None => format!("import {}", module),
+        };
+    }
+
+    fn names(&mut self, pair : Pair<'s, AstRule>) -> String {
+        let open = prefix(&self.punctuation(&["{"]));
+        let mut names = self.list(pair.into_inner());
+        names += &suffix(&self.punctuation(&[","])); // a trailing comma is left out
+        let close = suffix(&self.punctuation(&["}"]));
+        return format!("{}{{{}{}}}", open, names, close);
+    }
+
+    // the lines of a goal, a short goal is written on one line and a long goal has a line for each rule.
+    fn goal(&mut self, pair : Pair<'s, AstRule>) -> Vec<String> {
+        let mut inner = pair.into_inner().filter(|x| x.as_rule() != AstRule::doc_comment).peekable();
+        let head = self.compound(inner.next().unwrap());
+        let colon = prefix(&self.punctuation(&[":"]));
+        let first = inner.peek().map_or(self.source.len(), |x| x.as_span().start());
+        // the braces of a long goal come before its first rule, a rule may also begin with a brace (e.g. `{X} = S`)
+        if self.find(&["{"]).is_none_or(|(start, _)| start >= first) {
+            let (conditions, action) = self.rule(inner.next().unwrap());
+            return lines(&format!("{} {}: {}", head, colon, arrow(&conditions, &action, 0)));
+        }
+        let open = prefix(&self.punctuation(&["{"]));
+        let mut header = format!("{} {}: {}{{", head, colon, open);
+        let mut entries : Vec<Entry> = Vec::new();
+        for body in inner {
+            let mut entry = Entry::default();
+            for comment in self.trivia(start(&body)) {
+                match entries.last_mut() {
+                    _ if comment.own_line => {
+                        if comment.blank_before && !(entries.is_empty() && entry.leading.is_empty()) {
+                            entry.leading.push(String::new());
+                        }
+                        entry.leading.push(comment.text.to_string());
+                    },
+                    Some(previous) => previous.trailing += &format!(" {}", comment.text),
+                    None => header += &format!(" {}", comment.text),
+                }
+            }
+            entry.blank = self.newlines > 1 && !entries.is_empty();
+            (entry.conditions, entry.action) = self.rule(body);
+            // the separator, or a trailing comma after the last rule that is left out
+            entry.trailing = self.punctuation(&[","]).iter().map(|x| format!(" {}", x.text)).collect();
+            entries.push(entry);
+        }
+        let mut footer = Vec::new();
+        for comment in self.punctuation(&["}"]) {
+            match entries.last_mut() {
+                _ if comment.own_line => footer.push(comment.text.to_string()),
+                Some(previous) => previous.trailing += &format!(" {}", comment.text),
+                None => header += &format!(" {}", comment.text),
+            }
+        }
+        // the arrows of the rules that are written on one line are aligned
+        let width = entries.iter().filter(|x| !x.conditions.contains('\n')).map(|x| x.conditions.chars().count()).max().unwrap_or(0);
+        let mut result = vec![header];
+        for (i, entry) in entries.iter().enumerate() {
+            result.extend(entry.leading.iter().map(|x| indent(x)));
+            if entry.blank {
+                result.push(String::new());
+            }
+            let width = if entry.conditions.contains('\n') { 0 } else { width };
+            let separator = if i + 1 < entries.len() { "," } else { "" };
+            result.extend(lines(&format!("{}{}{}", arrow(&entry.conditions, &entry.action, width), separator, entry.trailing)).iter().map(|x| indent(x)));
+        }
+        result.extend(footer.iter().map(|x| indent(x)));
+        result.push("}".to_string());
+        return result;
+    }
+
+    // the conditions of a rule (and the comments before its arrow) and its action.
+    fn rule(&mut self, pair : Pair<'s, AstRule>) -> (String, String) {
+        let mut inner = pair.into_inner().filter(|x| x.as_rule() != AstRule::doc_comment);
+        let mut conditions = String::new();
+        for (i, alternative) in inner.next().unwrap().into_inner().enumerate() {
+            if i > 0 {
+                // either separator gives the same alternatives, `;` is the canonical one
+                conditions += &suffix(&self.punctuation(&[";", "||"]));
+                conditions += "; ";
+            }
+            conditions += &self.list(alternative.into_inner());
+        }
+        conditions += &suffix(&self.punctuation(&["->"]));
+        let action = self.term(inner.next().unwrap().into_inner().next().unwrap());
+        return (conditions.trim_end_matches(' ').to_string(), action);
+    }
+
+    // an expression or a condition, binary operators are written with a space on each side.
+    fn expression(&mut self, pair : Pair<'s, AstRule>) -> String {
+        let mut text = String::new();
+        for item in pair.into_inner() {
+            match item.as_rule() {
+                AstRule::gte => text += &format!(" {} ", self.token(&item, ">=")),
+                AstRule::lte => text += &format!(" {} ", self.token(&item, "<=")),
+                AstRule::eq | AstRule::neq | AstRule::gt | AstRule::lt | AstRule::and | AstRule::or | AstRule::add | AstRule::subtract
+                    | AstRule::multiply | AstRule::divide | AstRule::modulo | AstRule::concat | AstRule::member | AstRule::union
+                    | AstRule::intersect | AstRule::difference | AstRule::infix => text += &format!(" {} ", self.leaf(&item)),
+                AstRule::minus | AstRule::not => text += &self.leaf(&item),
+                AstRule::index => {
+                    text += &prefix(&self.punctuation(&["["]));
+                    let index = self.expression(item.into_inner().next().unwrap());
+                    text += &format!("[{}{}]", index, suffix(&self.punctuation(&["]"])));
+                },
+                AstRule::field => {
+                    text += &prefix(&self.punctuation(&["."]));
+                    text += &format!(".{}", self.leaf(&item.into_inner().next().unwrap()));
+                },
+                _ => text += &self.term(item),
+            }
+        }
+        return text;
+    }
+
+    // an operand of an expression, an item of a collection or an action.
+    fn term(&mut self, pair : Pair<'s, AstRule>) -> String {
+        return match pair.as_rule() {
+            // an expression in an expression is parenthesised
+            AstRule::expr | AstRule::lexpr => {
+                let open = prefix(&self.punctuation(&["("]));
+                let expression = self.expression(pair);
+                format!("{}({}{})", open, expression, suffix(&self.punctuation(&[")"])))
+            },
+            AstRule::call | AstRule::compound => self.compound(pair),
+            AstRule::list => self.collection(pair, "[", "]"),
+            AstRule::ulist | AstRule::obj => self.collection(pair, "{", "}"),
+            AstRule::tuple => self.collection(pair, "(", ")"),
+            AstRule::pipe_arg => self.term(pair.into_inner().next().unwrap()),
+            AstRule::spread => {
+                let star = prefix(&self.punctuation(&["*"]));
+                format!("{}*{}", star, self.leaf(&pair.into_inner().next().unwrap()))
+            },
+            AstRule::obj_arg => {
+                let mut inner = pair.into_inner();
+                let key = self.expression(inner.next().unwrap());
+                let colon = suffix(&self.punctuation(&[":"]));
+                format!("{}{}: {}", key, colon, self.expression(inner.next().unwrap()))
+            },
+            _ => self.leaf(&pair), // a name, a number, a string or a boolean
+        };
+    }
+
+    // e.g. `move(X, 1)`, a goal head, an action or a call of a function.
+    fn compound(&mut self, pair : Pair<'s, AstRule>) -> String {
+        let mut inner = pair.into_inner();
+        let name = self.leaf(&inner.next().unwrap());
+        let open = suffix(&self.punctuation(&["("]));
+        let arguments = self.list(inner);
+        return format!("{}{}({}{})", name, open, arguments, suffix(&self.punctuation(&[")"])));
+    }
+
+    // a list, an unordered list, an object or a tuple.
+    fn collection(&mut self, pair : Pair<'s, AstRule>, open : &str, close : &str) -> String {
+        if pair.clone().into_inner().next().is_none() {
+            return self.leaf(&pair); // `[]` or `{}`
+        }
+        let start = prefix(&self.punctuation(&[open]));
+        let mut inner = pair.into_inner().peekable();
+        let mut items = match inner.peek().map(Pair::as_rule) {
+            Some(AstRule::seq) => self.list(inner.next().unwrap().into_inner()),
+            _ => self.list(inner.by_ref()),
+        };
+        // the remaining items, e.g. `[X | T]`
+        if let Some(rest) = inner.next() {
+            items += &suffix(&self.punctuation(&["|"]));
+            items += &format!(" | {}", self.term(rest));
+        }
+        return format!("{}{}{}{}{}", start, open, items, suffix(&self.punctuation(&[close])), close);
+    }
+
+    // items separated by `, `.
+    fn list<I : Iterator<Item = Pair<'s, AstRule>>>(&mut self, items : I) -> String {
+        let mut text = String::new();
+        for (i, item) in items.enumerate() {
+            if i > 0 {
+                text += &suffix(&self.punctuation(&[","]));
+                text += ", ";
+            }
+            text += &match item.as_rule() {
+                AstRule::expr | AstRule::lexpr => self.expression(item),
+                _ => self.term(item),
+            };
+        }
+        return text;
+    }
+
+    // the text of `pair` (e.g. a name or a number) after the comments before it.
+    fn leaf(&mut self, pair : &Pair<'s, AstRule>) -> String {
+        return self.token(pair, pair.as_str());
+    }
+
+    // `text` in place of `pair` (e.g. `>=` for `> =`) after the comments before it.
+    fn token(&mut self, pair : &Pair<'s, AstRule>, text : &str) -> String {
+        let comments = self.trivia(pair.as_span().start());
+        self.position = self.position.max(pair.as_span().end());
+        return format!("{}{}", prefix(&comments), text);
+    }
+
+    // the comments before the next token if it is one of `tokens` (the punctuation between pairs, e.g. `->`), the token is
+    // then written. There are none if the next token is something else.
+    fn punctuation(&mut self, tokens : &[&str]) -> Vec<Comment<'s>> {
+        let Some((start, length)) = self.find(tokens) else {
+            return Vec::new();
+        };
+        let comments = self.trivia(start);
+        self.position = start + length;
+        return comments;
+    }
+
+    // where the next token is and its length if it is one of `tokens`.
+    fn find(&self, tokens : &[&str]) -> Option<(usize, usize)> {
+        let mut i = self.position;
+        while let Some(rest) = self.source.get(i..).filter(|x| !x.is_empty()) {
+            match comment(rest) {
+                0 if rest.starts_with(char::is_whitespace) => i += rest.chars().next().map_or(1, char::len_utf8),
+                0 => break,
+                length => i += length,
+            }
+        }
+        return tokens.iter().find(|x| self.source[i..].starts_with(*x)).map(|x| (i, x.len()));
+    }
+
+    // the comments between the source that has been written and `end`, the source up to `end` is then written.
+    fn trivia(&mut self, end : usize) -> Vec<Comment<'s>> {
+        let Some(gap) = self.source.get(self.position..end) else {
+            return Vec::new();
+        };
+        let mut comments = Vec::new();
+        let mut newlines = usize::from(self.position == 0); // the first comment of the source is on its own line
+        let mut i = 0;
+        while i < gap.len() {
+            let rest = &gap[i..];
+            match comment(rest) {
+                0 => {
+                    newlines += usize::from(rest.starts_with('\n'));
+                    i += rest.chars().next().map_or(1, char::len_utf8);
+                },
+                length => {
+                    comments.push(Comment { text : rest[..length].trim_end(), own_line : newlines > 0, blank_before : newlines > 1 });
+                    newlines = 0;
+                    i += length;
+                },
+            }
+        }
+        self.position = end;
+        self.newlines = newlines;
+        return comments;
+    }
+}
+
+// a rule of a long goal.
+#[derive(Default)]
+struct Entry {
+    leading : Vec<String>,  // the comments on the lines before it
+    blank : bool,           // there is a blank line before it
+    conditions : String,
+    action : String,
+    trailing : String,      // the comments after it on its line
+}
+
+// the length of the comment at the start of `text`, if there is one.
+pub(crate) fn comment(text : &str) -> usize {
+    if text.starts_with("//") {
+        return text.find('\n').unwrap_or(text.len());
+    }
+    if !text.starts_with("/*") {
+        return 0;
+    }
+    // block comments may be nested
+    let mut depth = 0;
+    let mut i = 0;
+    while i < text.len() {
+        if text[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if text[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    return text.len();
+}
+
+// where `pair` begins after its doc comment, which is written as the comments before it are.
+fn start(pair : &Pair<AstRule>) -> usize {
+    let mut inner = pair.clone().into_inner();
+    return match inner.next() {
+        Some(x) if x.as_rule() == AstRule::doc_comment => inner.next().map_or(pair.as_span().end(), |x| x.as_span().start()),
+        _ => pair.as_span().start(),
+    };
+}
+
+// comments written before a token, a line comment ends its line.
+fn prefix(comments : &[Comment]) -> String {
+    return comments.iter().map(|x| if x.text.starts_with("//") { format!("{}\n", x.text) } else { format!("{} ", x.text) }).collect();
+}
+
+// comments written after a token.
+fn suffix(comments : &[Comment]) -> String {
+    return comments.iter().map(|x| if x.text.starts_with("//") { format!(" {}\n", x.text) } else { format!(" {}", x.text) }).collect();
+}
+
+// a rule, its conditions padded to `width` so that the arrows of the rules of a goal are aligned.
+fn arrow(conditions : &str, action : &str, width : usize) -> String {
+    if conditions.is_empty() && width == 0 {
+        return format!("-> {}", action);
+    }
+    return format!("{:<width$} -> {}", conditions, action, width = width);
+}
+
+// the lines of `text`, those after a line comment are continued as they are.
+fn lines(text : &str) -> Vec<String> {
+    return text.split('\n').map(str::to_string).collect();
+}
+
+fn indent(line : &str) -> String {
+    if line.is_empty() {
+        return String::new();
+    }
+    return format!("{}{}", INDENT, line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::statement::Statement;
+
+    // a directory holding `files` (a path relative to the directory and its source), removed once the test is done.
+    struct Directory(PathBuf);
+
+    impl Directory {
+        fn new(name : &str, files : &[(&str, &str)]) -> Self {
+            let root = std::env::temp_dir().join(format!("teleora-format-{}-{}", name, std::process::id()));
+            for (path, source) in files {
+                let path = root.join(path);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, source).unwrap();
+            }
+            return Directory(root);
+        }
+    }
+
+    impl Drop for Directory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn canonical_layout() {
+        let source = "g(X):X>1->a(X,X)\n\n\n// long\nh(X) : {X > 1 -> a, // big\ntrue->b}";
+        let formatted = format(source, None, Registry::default()).unwrap();
+        assert_eq!(formatted, "g(X) : X > 1 -> a(X, X)\n\n// long\nh(X) : {\n    X > 1 -> a, // big\n    true  -> b\n}\n");
+        assert_eq!(format(&formatted, None, Registry::default()).unwrap(), formatted);
+        // alternatives separated by `||` are the same as those separated by `;`
+        let formatted = format("g(X) : X > 1 || X < 0; (X = 0 || X = 1) -> a", None, Registry::default()).unwrap();
+        assert_eq!(formatted, "g(X) : X > 1; X < 0; (X = 0 || X = 1) -> a\n");
+        // doc comments stay above what they document, a comment may end the source without a line break
+        let formatted = format("/// G.\ng(X):{\n/// A.\nX>1->a,true->b}\nh(X):X>1->c // last", None, Registry::default()).unwrap();
+        assert_eq!(formatted, "/// G.\ng(X) : {\n    /// A.\n    X > 1 -> a,\n    true  -> b\n}\nh(X) : X > 1 -> c // last\n");
+    }
+
+    #[test]
+    fn host_functions() {
+        let mut registry = Registry::default();
+        registry.function("angle_between", 2, |x| Ok(x[0].clone())).unwrap();
+        registry.operator("dot", 0, |x, _ : &Statement| Ok(x.clone())).unwrap();
+        let formatted = format("g(X):X dot 1>0->a(angle_between(X,1))", None, registry.clone()).unwrap();
+        assert_eq!(formatted, "g(X) : X dot 1 > 0 -> a(angle_between(X, 1))\n");
+        let (_, errors) = Loader::default().load_source(&formatted, None, registry.clone());
+        assert!(errors.is_empty(), "{:?}", errors);
+        // the text that could not be parsed is described with the registered operators
+        let error = format("g(X) : X > 1 dot", None, registry).unwrap_err();
+        assert!(matches!(&error, TeleoraError::Syntax { expected, .. } if expected[0] == "an expression"), "{:?}", error);
+    }
+
+    #[test]
+    fn relative_imports() {
+        let directory = Directory::new("imports", &[
+            ("main.tela", ""),
+            ("lib/nav.tela", "step(X) : true -> move(X)"),
+        ]);
+        let path = directory.0.join("main.tela");
+        let source = "import \"lib/nav.tela\"\ng(X):true->nav::step(X)";
+        let formatted = format(source, Some(&path), Registry::default()).unwrap();
+        assert_eq!(formatted, "import \"lib/nav.tela\"\ng(X) : true -> nav::step(X)\n");
+        let (_, errors) = Loader::default().load_source(&formatted, Some(&path), Registry::default());
+        assert!(errors.is_empty(), "{:?}", errors);
+        // without the path the module is not found, and the goal that calls it is not loaded
+        let (_, errors) = Loader::default().load_source(&formatted, None, Registry::default());
+        assert_eq!(errors[0].code(), "E0015");
+    }
+}
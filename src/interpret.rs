@@ -1,84 +1,244 @@
 
+use std::cell::RefCell;
 use itertools::Itertools;
 use pest::pratt_parser::{Assoc::*, Op, PrattParser};
+use pest::Parser;
 use pest::iterators::{Pair, Pairs};
-use crate::Rule as AstRule;
+use crate::{Rule as AstRule, TeleoraParser};
+use crate::error::{TeleoraError, Span, Failure};
+use crate::diagnostic::Diagnostic;
+use crate::analysis::{analyse, Finding};
+use crate::registry::Registry;
+use crate::module::Loader;
+use crate::format::comment;
 
-use crate::statement::frame::{Frame};
-use crate::statement::{Statement, Variable, Atom, Sequence, UnaryOperator, BinaryOperator, Integer, Float, AsStatement, List, UList, Object};
+use crate::statement::Frame;
+use crate::statement::frame::Continuation;
+use crate::statement::function::Function;
+use crate::statement::invert::{non_invertible, fold};
+use crate::statement::{Statement, Variable, Atom, Text, Compound, Boolean, Sequence, UnaryOperator, BinaryOperator, Integer, IntegerValue, Float, FloatValue, AsStatement, List, UList, Object, NumericEquality, Numbers, Symbol, Symbols, EvaluationError, Call};
 
 // pratt parser to handle operator prescendence
 lazy_static::lazy_static! {
     static ref PRATT_PARSER: PrattParser<AstRule> = {
-        use pest::pratt_parser::{Assoc::*, Op};
         use AstRule::*;
         // Precedence is defined lowest to highest
         PrattParser::<AstRule>::new()
             //.op(Op::infix(pipe, Left))
-            .op(Op::infix(eq, Left) | Op::infix(lt, Left) | Op::infix(gt, Left) | Op::infix(lte, Left) | Op::infix(gte, Left))
-            .op(Op::infix(or, Left) | Op::infix(and, Left))
-            .op(Op::infix(add, Left) | Op::infix(subtract, Left))
-            .op(Op::infix(multiply, Left) | Op::infix(divide, Left) | Op::infix(modulo, Left))
+            .op(Op::infix(or, Left))
+            .op(Op::infix(and, Left))
+            .op(Op::infix(eq, Left) | Op::infix(lt, Left) | Op::infix(gt, Left) | Op::infix(lte, Left) | Op::infix(gte, Left) | Op::infix(neq, Left) | Op::infix(member, Left))
+            .op(Op::infix(infix, Left))
+            .op(Op::infix(add, Left) | Op::infix(subtract, Left) | Op::infix(concat, Left) | Op::infix(union, Left) | Op::infix(difference, Left))
+            .op(Op::infix(multiply, Left) | Op::infix(divide, Left) | Op::infix(modulo, Left) | Op::infix(intersect, Left))
             .op(Op::prefix(minus) | Op::prefix(not))
+            .op(Op::postfix(index) | Op::postfix(field))
     };
 }
 
 #[derive(Debug)]
 pub struct Program {
-    goals : Vec<(String, Vec<Goal>)>,
+    goals : Vec<(Atom, Vec<Goal>)>,
+    numbers : Numbers,          // how numbers are compared during matching and divided
+    symbols : Symbols,          // names of the atoms and variables used by goals and observations
+    registry : Registry,        // functions and operators that goals and observations may use
+    warnings : Vec<Diagnostic>, // problems found while loading that do not prevent the program from running
+}
+impl PartialEq for Program {
+    // names are compared rather than symbols, so programs are equal whatever order their names were interned in
+    fn eq(&self, other: &Self) -> bool {
+        let translate = |x : Symbol| self.symbols.get(other.symbols.name(x)).unwrap_or(Symbol(u32::MAX)); // a name that `self` does not have
+        return self.goals.len() == other.goals.len() && self.goals.iter().zip(other.goals.iter()).all(|((name1, goals1), (name2, goals2))| {
+            *name1 == Atom(translate(name2.0)) && goals1.len() == goals2.len() && goals1.iter().zip(goals2.iter()).all(|(x, y)| *x == y.translate(&translate))
+        });
+    }
 }
 
 
-#[derive(Debug, PartialEq)]
-pub struct Action;
+// programs, goals, rules, conditions and actions are equal if they are written the same way, wherever they are written.
 
-#[derive(Debug, PartialEq)]
-pub struct Condition;
+#[derive(Debug)]
+pub struct Action(pub Statement, pub Span); // where the action is written in the source
+impl PartialEq for Action {
+    fn eq(&self, other: &Self) -> bool { self.0 == other.0 }
+}
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
+pub struct Condition(pub Statement, pub Span);
+impl PartialEq for Condition {
+    fn eq(&self, other: &Self) -> bool { self.0 == other.0 }
+}
+
+#[derive(Debug)]
 pub struct Rule { 
-    conditions : Vec<Condition>,
+    alternatives : Vec<Vec<Condition>>, // the rule applies if every condition of one of these holds, they are tried in order
     actions : Vec<Action>,
+    span : Span, // where the rule is written in the source
+    doc : Option<String>,
+}
+impl PartialEq for Rule {
+    fn eq(&self, other: &Self) -> bool { self.alternatives == other.alternatives && self.actions == other.actions && self.doc == other.doc }
 }
 
 #[derive(Debug)]
 pub struct Head {
     name : Atom, 
     arguments : Sequence,
+    span : Span,
 }
 impl PartialEq for Head {
     fn eq(&self, other: &Self) -> bool { self.name == other.name }
 }
 impl Default for Head { 
-    fn default() -> Self { return Head { name : Atom::default(), arguments : Sequence::default() } }
+    fn default() -> Self { return Head { name : Atom::default(), arguments : Sequence::default(), span : Span::default() } }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub struct Goal {
     head : Head, 
     body : Vec<Rule>,
+    slots : usize, // number of (named) variables in the goal
+    variables : Vec<Span>, // where each (named) variable is first written, by slot
+    span : Span, // where the goal is written in the source
+    doc : Option<String>,
+    expressions : Vec<(Statement, Span)>, // where each operator and call in the goal is written, innermost first
+}
+impl PartialEq for Goal {
+    fn eq(&self, other: &Self) -> bool {
+        self.head == other.head && self.head.arguments == other.head.arguments && self.body == other.body && self.slots == other.slots && self.doc == other.doc
+    }
 }
 impl Default for Goal { 
-    fn default() -> Self { return Goal { head : Head::default(), body : Vec::new() } }
+    fn default() -> Self { return Goal { head : Head::default(), body : Vec::new(), slots : 0, variables : Vec::new(), span : Span::default(), doc : None, expressions : Vec::new() } }
+}
+
+/// Used while interpreting parsed pairs, interns the names of atoms and variables. Variables are given a slot in order of 
+/// their first occurrence, variables with the same name share a slot.
+pub struct Context<'s> {
+    pub symbols : &'s mut Symbols,
+    pub registry : &'s Registry, // functions and operators are looked up by name
+    variables : Vec<Symbol>, // the variable in each slot
+    spans : Vec<Span>, // where the variable in each slot is first written
+    errors : Vec<TeleoraError>, // problems found while interpreting, e.g. invalid literals
+    expressions : Vec<(Statement, Span)>, // where each operator and call is written, innermost first
+}
+
+impl<'s> Context<'s> {
+    pub fn new(symbols : &'s mut Symbols, registry : &'s Registry) -> Self {
+        return Context { symbols : symbols, registry : registry, variables : Vec::new(), spans : Vec::new(), errors : Vec::new(), expressions : Vec::new() };
+    }
+
+    /// records a problem, interpreting continues so that the problem can be reported once interpreting is done.
+    pub fn error(&mut self, error : TeleoraError) {
+        self.errors.push(error);
+    }
+
+    /// the first problem found while interpreting, if there was one.
+    pub fn result(&self) -> Result<(), TeleoraError> {
+        return match self.errors.first() {
+            Some(e) => Err(e.clone()),
+            None => Ok(()),
+        };
+    }
+
+    /// every problem found while interpreting, in the order they were found.
+    pub fn take_errors(&mut self) -> Vec<TeleoraError> {
+        return std::mem::take(&mut self.errors);
+    }
+
+    pub fn atom(&mut self, name : &str) -> Atom {
+        return Atom(self.symbols.intern(name));
+    }
+
+    /// the variable named `name`, each named variable has its own slot. `span` is where it is written.
+    pub fn variable(&mut self, name : &str, span : Span) -> Variable {
+        if name == "_" {
+            return Variable::anonymous();
+        }
+        let symbol = self.symbols.intern(name);
+        let slot = match self.variables.iter().position(|x| *x == symbol) {
+            Some(slot) => slot,
+            None => { self.variables.push(symbol); self.spans.push(span); self.variables.len() - 1 },
+        };
+        return Variable { name : symbol, slot : Some(slot) };
+    }
+
+    /// the number of slots needed to hold the variables seen so far.
+    pub fn slots(&self) -> usize {
+        return self.variables.len();
+    }
+
+    /// where the variable in each slot is first written.
+    pub fn spans(&self) -> &[Span] {
+        return &self.spans;
+    }
+
+    /// records that `statement` is written at `span` if evaluating it may raise an error, i.e. it is an operator or a call.
+    pub fn expression(&mut self, statement : &Statement, span : &Span) {
+        if matches!(statement, Statement::BinaryOperator(_) | Statement::UnaryOperator(_) | Statement::Call(_)) {
+            self.expressions.push((statement.clone(), span.clone()));
+        }
+    }
+
+    /// where each operator and call seen so far is written, innermost first.
+    pub fn take_expressions(&mut self) -> Vec<(Statement, Span)> {
+        return std::mem::take(&mut self.expressions);
+    }
+}
+
+// implemented by each type that is interpreted from a parsed pair.
+pub trait Interpret {
+    fn interpret(pair : Pair<AstRule>, context : &mut Context) -> Self;
 }
 
-pub fn interpret_expression(expr : Pairs<AstRule>) -> Statement {
+pub fn interpret_expression(expr : Pairs<AstRule>, context : &mut Context) -> Statement {
     //println!("    {:?}", expr);
+    let context = RefCell::new(context); // shared by the primary and postfix operations, these are never interpreted at the same time
     let result = PRATT_PARSER
-        .map_primary(|primary| match primary.as_rule() {
-            AstRule::expr               => interpret_expression(primary.into_inner()),
-            AstRule::signed_integer     => Integer::from(primary).as_statement(),
-            AstRule::signed_float       => Float::from(primary).as_statement(),
-            AstRule::variable           => Variable::from(primary).as_statement(),
-            AstRule::atom               => Atom::from(primary).as_statement(),
-            AstRule::list               => List::from(primary).as_statement(),
-            AstRule::ulist              => UList::from(primary).as_statement(),
-            AstRule::seq                => Sequence::from(primary).as_statement(),
-            rule => unreachable!("{:?}", rule)
+        .map_primary(|primary| {
+            let nested = primary.as_rule() == AstRule::expr; // its expressions are recorded while interpreting it
+            let span = if nested { parenthesised(&primary) } else { tokens(&primary) };
+            let statement = match primary.as_rule() {
+                AstRule::expr               => interpret_expression(primary.into_inner(), *context.borrow_mut()),
+                AstRule::signed_integer     => match Integer::try_from(primary) {
+                    Ok(x) => x.as_statement(),
+                    Err(e) => { context.borrow_mut().error(e); Statement::Empty },
+                },
+                AstRule::signed_float       => match Float::try_from(primary) {
+                    Ok(x) => x.as_statement(),
+                    Err(e) => { context.borrow_mut().error(e); Statement::Empty },
+                },
+                AstRule::boolean            => Boolean::from(primary).as_statement(),
+                AstRule::string             => match Text::try_from(primary) {
+                    Ok(x) => x.as_statement(),
+                    Err(e) => { context.borrow_mut().error(e); Statement::Empty },
+                },
+                AstRule::call               => {
+                    let call = interpret_call(primary, *context.borrow_mut());
+                    match call {
+                        Ok(x) => x.as_statement(),
+                        Err(e) => { context.borrow_mut().error(e); Statement::Empty },
+                    }
+                },
+                AstRule::variable           => Variable::interpret(primary, *context.borrow_mut()).as_statement(),
+                AstRule::atom               => Atom::interpret(primary, *context.borrow_mut()).as_statement(),
+                AstRule::list               => List::interpret(primary, *context.borrow_mut()).as_statement(),
+                AstRule::ulist              => UList::interpret(primary, *context.borrow_mut()).as_statement(),
+                AstRule::obj                => Object::interpret(primary, *context.borrow_mut()).as_statement(),
+                AstRule::seq                => Sequence::interpret(primary, *context.borrow_mut()).as_statement(),
+                AstRule::tuple              => Sequence::interpret(primary, *context.borrow_mut()).as_statement(),
+                rule => { context.borrow_mut().error(unexpected("an expression", rule, primary.as_span())); Statement::Empty },
+            };
+            if !nested {
+                context.borrow_mut().expression(&statement, &span);
+            }
+            return (statement, 0, span);
         })
-        .map_infix(|lhs, op, rhs| {
-            match op.as_rule() {
+        .map_infix(|(lhs, spine, left), op, (rhs, _, right)| {
+            let span = Span { end : right.end, ..left };
+            let statement = match op.as_rule() {
                 AstRule::eq         => Statement::BinaryOperator(BinaryOperator::Equal(Box::new(lhs), Box::new(rhs))),
+                AstRule::neq        => Statement::BinaryOperator(BinaryOperator::NotEqual(Box::new(lhs), Box::new(rhs))),
                 AstRule::gt         => Statement::BinaryOperator(BinaryOperator::GreaterThan(Box::new(lhs), Box::new(rhs))),
                 AstRule::gte        => Statement::BinaryOperator(BinaryOperator::GreaterThanEqual(Box::new(lhs), Box::new(rhs))),
                 AstRule::lt         => Statement::BinaryOperator(BinaryOperator::LessThan(Box::new(lhs), Box::new(rhs))),
@@ -90,144 +250,547 @@ pub fn interpret_expression(expr : Pairs<AstRule>) -> Statement {
                 AstRule::multiply   => Statement::BinaryOperator(BinaryOperator::Multiply(Box::new(lhs), Box::new(rhs))),
                 AstRule::divide     => Statement::BinaryOperator(BinaryOperator::Divide(Box::new(lhs), Box::new(rhs))),
                 AstRule::modulo     => Statement::BinaryOperator(BinaryOperator::Modulo(Box::new(lhs), Box::new(rhs))),
-                rule => unreachable!("Expected infix operation, found {:?}", rule),
-            }
+                AstRule::concat     => Statement::BinaryOperator(BinaryOperator::Concat(Box::new(lhs), Box::new(rhs))),
+                AstRule::member     => Statement::BinaryOperator(BinaryOperator::In(Box::new(lhs), Box::new(rhs))),
+                AstRule::union      => Statement::BinaryOperator(BinaryOperator::Union(Box::new(lhs), Box::new(rhs))),
+                AstRule::intersect  => Statement::BinaryOperator(BinaryOperator::Intersect(Box::new(lhs), Box::new(rhs))),
+                AstRule::difference => Statement::BinaryOperator(BinaryOperator::Difference(Box::new(lhs), Box::new(rhs))),
+                AstRule::infix      => {
+                    let registry = context.borrow().registry;
+                    match registry.get_operator(op.as_str()) {
+                        Some(function) => {
+                            let (statement, spine) = insert(lhs, spine, function, rhs);
+                            context.borrow_mut().expression(&statement, &span); // only the whole chain, it may have been rotated
+                            return (statement, spine, span);
+                        },
+                        None => {
+                            context.borrow_mut().error(TeleoraError::UnknownOperator { span : op.as_span().into(), name : op.as_str().to_string(), known : registry.operator_names() });
+                            Statement::Empty
+                        },
+                    }
+                },
+                rule => { context.borrow_mut().error(unexpected("an infix operator", rule, op.as_span())); Statement::Empty },
+            };
+            context.borrow_mut().expression(&statement, &span);
+            return (statement, 0, span);
         })
-        .map_prefix(|op, rhs| {
-            match op.as_rule() {
+        .map_prefix(|op, (rhs, _, right)| {
+            let span = Span { end : right.end, ..Span::from(op.as_span()) };
+            let statement = match op.as_rule() {
                 AstRule::minus  => Statement::UnaryOperator(UnaryOperator::Minus(Box::new(rhs))),
                 AstRule::not    => Statement::UnaryOperator(UnaryOperator::Not(Box::new(rhs))),
-                rule => unreachable!("Expected prefix operation, found {:?}", rule),
-            }
+                rule => { context.borrow_mut().error(unexpected("a prefix operator", rule, op.as_span())); Statement::Empty },
+            };
+            context.borrow_mut().expression(&statement, &span);
+            return (statement, 0, span);
+        })
+        .map_postfix(|(lhs, _, left), op| {
+            let span = Span { end : tokens(&op).end, ..left };
+            let statement = match op.as_rule() {
+                AstRule::index  => Statement::BinaryOperator(BinaryOperator::Index(Box::new(lhs), Box::new(interpret_expression(op.into_inner(), *context.borrow_mut())))),
+                AstRule::field  => {
+                    let key = op.as_str()[1..].trim_start(); // `.name`
+                    Statement::BinaryOperator(BinaryOperator::Index(Box::new(lhs), Box::new(context.borrow_mut().atom(key).as_statement())))
+                },
+                rule => { context.borrow_mut().error(unexpected("a postfix operator", rule, op.as_span())); Statement::Empty },
+            };
+            context.borrow_mut().expression(&statement, &span);
+            return (statement, 0, span);
         })
         .parse(expr);
-    return result;
+    return result.0;
+}
+
+// `lhs name rhs` where `lhs` ends with `spine` operators registered by the host that are not parenthesised (e.g. `A cross B`).
+// The grammar gives these operators the same precedence, so one that binds more tightly than those before it takes their
+// right operand instead, e.g. `A cross B dot C` is `A cross (B dot C)` if `dot` has the higher precedence.
+fn insert(lhs : Statement, spine : usize, function : &Function, rhs : Statement) -> (Statement, usize) {
+    return match lhs {
+        Statement::Call(call) if spine > 0 && call.function.infix < function.infix => {
+            let (right, spine) = insert(call.arguments[1].clone(), spine - 1, function, rhs);
+            let arguments = Sequence::new(vec![call.arguments[0].clone(), right]);
+            (Call { function : call.function, arguments : arguments }.as_statement(), spine + 1)
+        },
+        lhs => (Call { function : function.clone(), arguments : Sequence::new(vec![lhs, rhs]) }.as_statement(), 1),
+    };
 }
 
 
 impl Program {
 
-    pub fn new(mut document : Pairs<AstRule>) -> Self {
-        let goal_rules = document.next().unwrap().into_inner();
-        let glen = goal_rules.len();
+    /// parses and loads a program that may use the builtin functions.
+    pub fn parse(source : &str) -> Result<Self, TeleoraError> {
+        return Program::parse_with_registry(source, Registry::default());
+    }
+
+    /// parses and loads a program that may use the functions and operators of `registry`.
+    pub fn parse_with_registry(source : &str, registry : Registry) -> Result<Self, TeleoraError> {
+        pest::set_error_detail(true); // so that syntax errors can describe the tokens that were expected
+        let document = TeleoraParser::parse(AstRule::document, source).map_err(|e| TeleoraError::syntax(e, source))?;
+        return Program::with_registry(document, registry);
+    }
+
+    /// loads a parsed program that may use the builtin functions.
+    pub fn new(document : Pairs<AstRule>) -> Result<Self, TeleoraError> {
+        return Program::with_registry(document, Registry::default());
+    }
+
+    /// parses and loads a program that may use the functions and operators of `registry`, recovering from errors so that all
+    /// of them are found rather than only the first. Goals with errors are left out of the program, the others are loaded.
+    /// Modules that the program imports are found relative to the current directory, see `Loader` for more control.
+    pub fn parse_recovering(source : &str, registry : Registry) -> (Self, Vec<TeleoraError>) {
+        return Loader::default().load_source(source, None, registry);
+    }
+
+    /// loads a parsed program (a `document`) that may use the functions and operators of `registry`.
+    pub fn with_registry(document : Pairs<AstRule>, registry : Registry) -> Result<Self, TeleoraError> {
+        let (program, errors) = Loader::default().load_document(document, None, registry);
+        return match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(program),
+        };
+    }
+
+    // the program made of `goals` (those of every module, the first goal is evaluated by default) that were loaded without
+    // `errors`. Goals with variables that can never be bound are left out, their errors are added to the others.
+    pub(crate) fn assemble(mut goals : Vec<Goal>, symbols : Symbols, registry : Registry, mut errors : Vec<TeleoraError>) -> (Self, Vec<TeleoraError>) {
+        let mut warnings = Vec::new();
+        goals.retain(|goal| {
+            let name = symbols.name(goal.get_name().0);
+            let mut bound = true;
+            for finding in analyse(goal, &symbols) {
+                match finding {
+                    Finding::Unbound(variable, statement, span) => {
+                        let variable = symbols.name(variable.name).to_string();
+                        errors.push(TeleoraError::UnboundVariable { span : span, goal : name.to_string(), variable : variable, statement : symbols.display(&statement).to_string() });
+                        bound = false;
+                    },
+                    Finding::Singleton(variable, span) => {
+                        let message = format!("`{}` is used only once in goal `{}`", symbols.name(variable.name), name);
+                        warnings.push(Diagnostic::warning("W0001", message, Some(span)).with_help("use `_` instead (or a name beginning with `_`)".to_string()));
+                    },
+                }
+            }
+            return bound;
+        });
         // group goals so that they may be combined if necessary.
-        let goals : Vec<(String, Vec<Goal>)> = goal_rules
-            .into_iter().take(glen - 1) // ignore EOI
-            .map(|goal| Goal::new(goal))
-            .group_by(|goal| goal.get_name())
+        let goals : Vec<(Atom, Vec<Goal>)> = goals
+            .into_iter()
+            .chunk_by(|goal| goal.get_name())
             .into_iter()
             .map(|(name, group)| (name, group.collect()))
             .collect();
-        return Program { goals: goals };
+        return (Program { goals: goals, numbers : Numbers::default(), symbols : symbols, registry : registry, warnings : warnings }, errors);
     }
 
-    pub fn interpret_observation(mut observation : Pairs<AstRule>) -> Result<Sequence, &str> {
-        if observation.len() > 1 {
-            return Result::Err("Received more than one observation to parse.");
-        }
-        return match observation.next() {
-            Some(seq) => Result::Ok(Sequence::from(seq)),
-            None => Result::Err("Received no observations to parse."),
+    /// problems found while loading the program that do not prevent it from running (e.g. singleton variables).
+    pub fn warnings(&self) -> &[Diagnostic] {
+        return &self.warnings;
+    }
+
+    /// the names of atoms and variables used by this program.
+    pub fn symbols(&self) -> &Symbols {
+        return &self.symbols;
+    }
+
+    /// the functions and operators that this program may use.
+    pub fn registry(&self) -> &Registry {
+        return &self.registry;
+    }
+
+    /// sets the policy used to compare numbers when matching, e.g. `NumericEquality::tolerant(1e-6, 1e-6)` for noisy sensor data.
+    pub fn set_equality(&mut self, equality : NumericEquality) {
+        self.numbers.equality = equality;
+    }
+
+    pub fn get_equality(&self) -> NumericEquality {
+        return self.numbers.equality;
+    }
+
+    /// sets how this program computes with numbers, e.g. `Numbers::rational()` to divide integers exactly.
+    pub fn set_numbers(&mut self, numbers : Numbers) {
+        self.numbers = numbers;
+    }
+
+    pub fn numbers(&self) -> Numbers {
+        return self.numbers;
+    }
+
+    /// parses an observation, a sequence of values separated by `,` (e.g. `1, [a, b]`).
+    pub fn parse_observation(&mut self, source : &str) -> Result<Sequence, TeleoraError> {
+        pest::set_error_detail(true);
+        let observation = TeleoraParser::parse(AstRule::observation, source).map_err(|e| TeleoraError::syntax(e, source))?;
+        return self.interpret_observation(observation);
+    }
+
+    /// interprets a parsed observation.
+    pub fn interpret_observation(&mut self, mut observation : Pairs<'_, AstRule>) -> Result<Sequence, TeleoraError> {
+        let observation = match observation.next() {
+            Some(pair) if pair.as_rule() == AstRule::observation => pair,
+            Some(pair) => return Err(unexpected("an observation", pair.as_rule(), pair.as_span())),
+            None => return Err(TeleoraError::Unexpected { span : Span::default(), expected : "an observation".to_string(), found : "nothing".to_string() }),
         };
+        let mut context = Context::new(&mut self.symbols, &self.registry);
+        let sequence = Sequence::interpret(observation, &mut context);
+        context.result()?;
+        return Ok(sequence);
     }
 
-    pub fn evaluate(&self, input : Sequence) -> Result<Action, &str> {
-        let (_, top_goals) = &self.goals[0];
-        for goal in top_goals {
-            // the input is cloned defensively, but it probably doesnt need to be... maybe use a ref? 
-            if let Some(action) = goal.evaluate(&input) { // this goal succeeded and returned an action !
-                return Ok(action);
+    /// evaluates the first goal of the program with `input` (e.g. an observation) to obtain an action.
+    pub fn evaluate(&self, input : Sequence) -> Result<Action, TeleoraError> {
+        let Some((name, _)) = self.goals.first() else {
+            return Err(TeleoraError::NoGoals);
+        };
+        return self.evaluate_top(*name, input);
+    }
+
+    /// evaluates the goal called `name` with `input` to obtain an action.
+    pub fn evaluate_named(&self, name : &str, input : Sequence) -> Result<Action, TeleoraError> {
+        return match self.symbols.get(name) {
+            Some(symbol) if self.has_goal(Atom(symbol)) => self.evaluate_top(Atom(symbol), input),
+            _ => Err(TeleoraError::UnknownGoal { name : name.to_string() }),
+        };
+    }
+
+    fn evaluate_top(&self, name : Atom, input : Sequence) -> Result<Action, TeleoraError> {
+        // variables are local to a goal, any that appear in the observation are unrelated to those of the goal.
+        let input = Sequence::new(input.iter().map(|x| x.anonymise()).collect());
+        return match self.evaluate_goal(name, &input) {
+            Ok(Some(action)) => Ok(action), // the top goal succeeded and returned an action !
+            Ok(None) => Err(TeleoraError::NoAction),
+            Err(failure) => Err(TeleoraError::Evaluation { message : failure.error.message(&self.symbols), error : failure.error, span : failure.span, trace : failure.trace }),
+        };
+    }
+
+    /// evaluates each goal called `name` in order until one of them produces an action, an error stops evaluation.
+    pub fn evaluate_goal(&self, name : Atom, input : &Sequence) -> Result<Option<Action>, Failure> {
+        for (_, goals) in self.goals.iter().filter(|(gname, _)| *gname == name) {
+            for goal in goals {
+                if let Some(action) = goal.evaluate(input, self)? {
+                    return Ok(Some(action));
+                }
             }
         }
-        return Err("Failed to obtain an action, did you forget to use the default rule?");
+        return Ok(None);
+    }
+
+    /// the goals of this program in the order they are written, those of the modules that it imports follow its own.
+    pub fn goals(&self) -> impl Iterator<Item = &Goal> {
+        return self.goals.iter().flat_map(|(_, goals)| goals.iter());
+    }
+
+    pub fn has_goal(&self, name : Atom) -> bool {
+        return self.goals.iter().any(|(gname, _)| *gname == name);
     }
 }
 
 impl Goal { 
-    pub fn new(pair : Pair<AstRule>) -> Goal { 
+    /// a goal, or every problem found while interpreting it.
+    pub fn new(pair : Pair<AstRule>, symbols : &mut Symbols, registry : &Registry) -> Result<Goal, Vec<TeleoraError>> { 
         //println!("----> {:?}", rule);
-        let mut pairsinner = pair.into_inner();
-        let head : Head = Head::new(pairsinner.next().unwrap()); // head can only take the form of a method
-        let body : Vec<Rule> = pairsinner.map(|p| Rule::new(p)).collect(); 
-        return Goal { head : head, body : body };
+        let mut context = Context::new(symbols, registry); // variables are numbered per goal
+        let (doc, whole, mut pairsinner) = documented(pair);
+        let span = Span::from(whole);
+        let head : Head = Head::new(next(&mut pairsinner, "a head", whole).map_err(|e| vec![e])?, &mut context).map_err(|e| vec![e])?; // head can only take the form of a method
+        let mut body : Vec<Rule> = Vec::new();
+        for pair in pairsinner {
+            match pair.as_rule() {
+                AstRule::invalid_body => context.error(skipped(pair, AstRule::body_only, context.registry)),
+                _ => body.push(Rule::new(pair, &mut context)),
+            }
+        }
+        let errors = context.take_errors();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let goal = Goal { head : head, body : body, slots : context.slots(), variables : context.spans().to_vec(), span : span, doc : doc, expressions : context.take_expressions() };
+        // the remaining items of an unordered collection cannot be shared between captures.
+        let unordered = |x : &Statement| matches!(x, Statement::UList(_) | Statement::Object(_)) && x.spreads() > 1;
+        if let Some(collection) = goal.statements().find_map(|x| x.find(&unordered)) {
+            return Err(vec![TeleoraError::SharedCapture { span : goal.span.clone(), goal : context.symbols.name(goal.get_name().0).to_string(), collection : context.symbols.display(collection).to_string() }]);
+        }
+        return Ok(goal);
     }
 
-    pub fn evaluate(&self, input : &Sequence) -> Option<Action> {
-        // create a new frame for this goal.
-        
-        for x in Frame::evaluate_sequence(&self.head.arguments.as_ref(), &input.as_ref(), &Frame::new()) {
-            println!("----------{:?}", x)
+    /// the statements that make up this goal, its head arguments followed by the conditions and actions of each rule.
+    pub fn statements(&self) -> impl Iterator<Item = &Statement> {
+        let body = self.body.iter().flat_map(|rule| rule.alternatives.iter().flatten().map(|x| &x.0).chain(rule.actions.iter().map(|x| &x.0)));
+        return self.head.arguments.iter().chain(body);
+    }
+
+    pub fn evaluate(&self, input : &Sequence, program : &Program) -> Result<Option<Action>, Failure> {
+        // rules are tried in order, the first rule whose conditions hold under some match of the head produces the action.
+        for rule in self.body.iter() {
+            let mut frame = Frame::with_numbers(self.slots, program.numbers()); // create a new frame for this goal.
+            let mut action = None;
+            frame.evaluate_sequence(&self.head.arguments.items, &input.items, &mut |frame| {
+                return match rule.evaluate(frame, program) {
+                    Ok(result) => { action = result; action.is_some() },
+                    Err(error) => frame.abort(error),
+                };
+            });
+            // an error that no condition or action claimed happened while matching the head
+            frame.locate(&self.head.span);
+            if let Some(error) = frame.take_error() {
+                return Err(self.narrow(error));
+            }
+            if action.is_some() {
+                return Ok(action);
+            }
         }
+        return Ok(None);
+    }
 
-        
+    // the failure located at the expression that raised it, if it was raised in this goal (rather than in a goal that its
+    // actions call) and the expression can be told from the values in the error.
+    fn narrow(&self, mut failure : Failure) -> Failure {
+        if let (Some(span), true) = (&failure.span, failure.trace.is_empty()) {
+            let within = |x : &Span| span.start <= x.start && x.end <= span.end;
+            if let Some((_, x)) = self.expressions.iter().find(|(x, y)| within(y) && failure.error.raised_by(x)) {
+                failure.span = Some(x.clone());
+            }
+        }
+        return failure;
+    }
+
+    pub fn get_name(&self) -> Atom {
+        return self.head.name;
+    }
+
+    pub fn span(&self) -> &Span {
+        return &self.span;
+    }
+
+    /// where `variable` is first written in this goal, or where the goal is if it is anonymous.
+    pub fn variable_span(&self, variable : &Variable) -> &Span {
+        return variable.slot.and_then(|slot| self.variables.get(slot)).unwrap_or(&self.span);
+    }
+
+    /// the doc comment written above this goal (its `///` lines without the `///`), e.g. for an editor to display.
+    pub fn doc(&self) -> Option<&str> {
+        return self.doc.as_deref();
+    }
+
+    /// renames this goal to `name` and each goal that its actions call with `rename`, e.g. to qualify them by their module.
+    pub(crate) fn rename(&mut self, name : Atom, mut rename : impl FnMut(Atom, &Span) -> Atom) {
+        self.head.name = name;
+        for action in self.body.iter_mut().flat_map(|rule| rule.actions.iter_mut()) {
+            match &mut action.0 {
+                Statement::Atom(x) => *x = rename(*x, &action.1),
+                Statement::Compound(x) => x.name = rename(x.name, &action.1),
+                _ => (), // a variable, the goal that it calls is only known once it is bound
+            }
+        }
+    }
 
+    /// names the source that this goal is in, see `Span::in_file`.
+    pub(crate) fn in_file(&mut self, file : &str) {
+        let name = |span : &mut Span| *span = std::mem::take(span).in_file(file);
+        name(&mut self.span);
+        name(&mut self.head.span);
+        self.variables.iter_mut().for_each(name);
+        self.expressions.iter_mut().for_each(|x| name(&mut x.1));
+        for rule in self.body.iter_mut() {
+            name(&mut rule.span);
+            rule.alternatives.iter_mut().flatten().for_each(|x| name(&mut x.1));
+            rule.actions.iter_mut().for_each(|x| name(&mut x.1));
+        }
+    }
+
+    pub fn head(&self) -> &Head {
+        return &self.head;
+    }
 
-        return None;
+    pub fn body(&self) -> &[Rule] {
+        return &self.body;
     }
 
-    pub fn get_name(&self) -> String {
-        return self.head.name.0.clone();
+    // this goal with the symbol of each atom and variable replaced by `f` of it, see `Statement::translate`.
+    fn translate<F : Fn(Symbol) -> Symbol>(&self, f : &F) -> Goal {
+        let conditions = |x : &Vec<Condition>| x.iter().map(|y| Condition(y.0.translate(f), y.1.clone())).collect();
+        let body = self.body.iter().map(|rule| Rule {
+            alternatives : rule.alternatives.iter().map(conditions).collect(),
+            actions : rule.actions.iter().map(|x| Action(x.0.translate(f), x.1.clone())).collect(),
+            span : rule.span.clone(),
+            doc : rule.doc.clone(),
+        }).collect();
+        let arguments = Sequence::new(self.head.arguments.iter().map(|x| x.translate(f)).collect());
+        let head = Head { name : Atom(f(self.head.name.0)), arguments : arguments, span : self.head.span.clone() };
+        return Goal { head : head, body : body, slots : self.slots, variables : self.variables.clone(), span : self.span.clone(), doc : self.doc.clone(), expressions : self.expressions.iter().map(|(x, y)| (x.translate(f), y.clone())).collect() };
     }
 }
 
 impl Head {
-    pub fn new(pair : Pair<AstRule>) -> Head {
+    pub fn new(pair : Pair<AstRule>, context : &mut Context) -> Result<Head, TeleoraError> {
+        let whole = pair.as_span();
+        let span = Span::from(whole);
         let mut pairsinner = pair.into_inner();
-        let name = Atom(pairsinner.next().unwrap().as_str().to_string()); // this should be an atom... 
-        let arguments : Vec<Statement> = pairsinner.into_iter().map(|arg| interpret_expression(arg.into_inner())).collect();
-        return Head { name : name, arguments : Sequence::new(arguments)};
+        let name = Atom::interpret(next(&mut pairsinner, "the name of the goal", whole)?, context);
+        let mut arguments : Vec<Statement> = Vec::new();
+        for arg in pairsinner {
+            // arithmetic in the head is matched by solving for its variable, so it must be invertible.
+            let argument = interpret_expression(arg.clone().into_inner(), context);
+            if non_invertible(&argument).is_some() {
+                return Err(TeleoraError::InvalidHead { span : arg.as_span().into(), goal : context.symbols.name(name.0).to_string(), argument : arg.as_str().to_string() });
+            }
+            arguments.push(fold(&argument));
+        }
+        return Ok(Head { name : name, arguments : Sequence::new(arguments), span : span });
     }
 
     pub fn len(&self) -> usize {
         return self.arguments.len();
     }
+
+    pub fn is_empty(&self) -> bool {
+        return self.arguments.is_empty();
+    }
+
+    pub fn arguments(&self) -> &Sequence {
+        return &self.arguments;
+    }
+
+    pub fn span(&self) -> &Span {
+        return &self.span;
+    }
 }
 
 
-impl From<Pair<'_, AstRule>> for Action {
-    fn from(pair: Pair<AstRule>) -> Self {
-        let mut pairsinner = pair.into_inner(); // TODO
-        return Action;
+impl Interpret for Action {
+    fn interpret(pair: Pair<AstRule>, context : &mut Context) -> Self {
+        let span = Span::from(pair.as_span());
+        let action = match pair.as_rule() {
+            AstRule::compound   => Compound::interpret(pair, context).as_statement(),
+            AstRule::atom       => Atom::interpret(pair, context).as_statement(),
+            AstRule::variable   => Variable::interpret(pair, context).as_statement(),
+            rule => { context.error(unexpected("an action", rule, pair.as_span())); Statement::Empty },
+        };
+        return Action(action, span);
     }
 }
 
-impl From<Pair<'_, AstRule>> for Condition { 
-    fn from(pair : Pair<AstRule>) -> Condition { 
-       
-        let condition = interpret_expression(pair.into_inner());
-        println!("----> {:?}", condition);
-        // evaluate this condition if possible...
+impl Action {
+    /// grounds this action using `frame`, if the action names a goal then the goal is evaluated (as a subgoal) to obtain the action.
+    pub fn evaluate(&self, frame : &Frame, program : &Program) -> Result<Option<Action>, Failure> {
+        let action = match frame.substitute(&self.0) {
+            Statement::Compound(x) => {
+                // variables are local to a goal, so any that are still unbound cannot be shared with a subgoal.
+                let arguments = x.arguments.iter().map(|y| Ok(y.evaluate_ground(&frame.numbers)?.anonymise())).collect::<Result<_, EvaluationError>>()
+                    .map_err(|error| Failure::from(error).called_from(&self.1))?;
+                Compound { name : x.name, arguments : Sequence::new(arguments) }.as_statement()
+            },
+            x => x,
+        };
+        let (name, arguments) = match &action {
+            Statement::Atom(x)      => (x, Sequence::default()),
+            Statement::Compound(x)  => (&x.name, x.arguments.clone()),
+            x => return Err(Failure::from(EvaluationError::NotAction(x.clone())).called_from(&self.1)),
+        };
+        if program.has_goal(*name) {
+            return program.evaluate_goal(*name, &arguments).map_err(|error| error.called_from(&self.1));
+        }
+        return Ok(Some(Action(action, self.1.clone())));
+    }
+}
+
+impl Interpret for Condition { 
+    fn interpret(pair : Pair<AstRule>, context : &mut Context) -> Condition { 
+        let span = tokens(&pair);
+        let condition = interpret_expression(pair.into_inner(), context);
+        return Condition(condition, span);
+    }
+}
 
-        return Condition;
-       
+impl Condition {
+    /// evaluates this condition under `frame`, calling `k` for each way in which the condition holds.
+    pub fn evaluate(&self, frame : &mut Frame, k : &mut Continuation) -> bool {
+        let result = frame.evaluate_constraint(&self.0, k);
+        frame.locate(&self.1);
+        return result;
     }
 }
 
 impl Rule {
-    pub fn new(pair : Pair<AstRule>) -> Rule { 
+    pub fn alternatives(&self) -> &[Vec<Condition>] {
+        return &self.alternatives;
+    }
+
+    pub fn actions(&self) -> &[Action] {
+        return &self.actions;
+    }
+
+    pub fn span(&self) -> &Span {
+        return &self.span;
+    }
+
+    /// the doc comment written above this rule, e.g. one of the rules of a long goal.
+    pub fn doc(&self) -> Option<&str> {
+        return self.doc.as_deref();
+    }
+
+    pub fn new(pair : Pair<AstRule>, context : &mut Context) -> Rule { 
         //println!("----> {:?}", rule);
-        let mut pairsinner = pair.into_inner();
-        let conditions : Vec<Condition> = pairsinner.next().unwrap().into_inner().map(|p| Condition::from(p)).collect();
-        let actions : Vec<Action> = pairsinner.next().unwrap().into_inner().map(|p| Action::from(p)).collect();
-        return Rule { conditions : conditions, actions : actions};
+        let (doc, whole, mut pairsinner) = documented(pair);
+        let span = Span::from(whole);
+        let alternatives : Vec<Vec<Condition>> = match next(&mut pairsinner, "conditions", whole) {
+            Ok(conditions) => conditions.into_inner()
+                .map(|alternative| alternative.into_inner().map(|p| Condition::interpret(p, context)).collect())
+                .collect(),
+            Err(e) => { context.error(e); Vec::new() },
+        };
+        let actions : Vec<Action> = match next(&mut pairsinner, "an action", whole) {
+            Ok(actions) => actions.into_inner().map(|p| Action::interpret(p, context)).collect(),
+            Err(e) => { context.error(e); Vec::new() },
+        };
+        return Rule { alternatives : alternatives, actions : actions, span : span, doc : doc };
+    }
+
+    pub fn evaluate(&self, frame : &mut Frame, program : &Program) -> Result<Option<Action>, Failure> {
+        let mut action = None;
+        // alternatives are tried in order, as if each were a separate rule with the same actions
+        self.alternatives.iter().any(|conditions| Rule::evaluate_conditions(conditions, frame, &mut |frame| {
+            // conditions whose variables were never bound cannot be known to hold
+            if frame.suspended().next().is_some() {
+                return false;
+            }
+            // index expressions in an action are replaced by their elements first
+            return self.actions.iter().any(|x| frame.resolve(&x.0, &mut |frame, a| {
+                match Action(a.clone(), x.1.clone()).evaluate(frame, program) {
+                    Ok(result) => { action = result; action.is_some() },
+                    Err(error) => frame.abort(error),
+                }
+            }));
+        }));
+        if let Some(error) = frame.take_error() {
+            return Err(error);
+        }
+        return Ok(action);
+    }
+
+    // conditions are evaluated in order, each using the bindings made by those before it.
+    fn evaluate_conditions(conditions : &[Condition], frame : &mut Frame, k : &mut Continuation) -> bool {
+        return match conditions.split_first() {
+            Some((condition, rest)) => condition.evaluate(frame, &mut |frame| Rule::evaluate_conditions(rest, frame, k)),
+            None => k(frame),
+        };
     }
 }
 
 
 // implement interpret for each Statement type.
 
-impl From<Pair<'_, AstRule>> for List { 
-    fn from(pair : Pair<AstRule>) -> Self {
+impl Interpret for List { 
+    fn interpret(pair : Pair<AstRule>, context : &mut Context) -> Self {
         let mut pairsinner = pair.into_inner();
         if let Some(lhead) = pairsinner.next() {
-            let mut seq = match lhead.as_rule() {
-                AstRule::seq => Sequence::from(lhead),
-                rule => unreachable!("Expected sequence, found {:?}", rule),
+            let seq = match lhead.as_rule() {
+                AstRule::seq => Sequence::interpret(lhead, context),
+                rule => { context.error(unexpected("a sequence", rule, lhead.as_span())); Sequence::default() },
             };
             if let Some(ltail) = pairsinner.next() {
-                let tail = interpret_expression(ltail.into_inner());
-                seq.items.push(tail);
-                return List::new(seq, true);
+                let tail = interpret_expression(ltail.into_inner(), context);
+                let items = seq.iter().cloned().chain(std::iter::once(tail)).collect();
+                return List::new(Sequence::new(items), true);
             } 
             return List::new(seq, false);
         }
@@ -236,51 +799,652 @@ impl From<Pair<'_, AstRule>> for List {
 }
 
 
-impl From<Pair<'_, AstRule>> for UList { 
-    fn from(pair : Pair<AstRule>) -> Self {
-        return List::from(pair).into();
+impl Interpret for UList { 
+    fn interpret(pair : Pair<AstRule>, context : &mut Context) -> Self {
+        return List::interpret(pair, context).into();
     }
 }
 
-impl From<Pair<'_, AstRule>> for Sequence { 
-    fn from(pair : Pair<AstRule>) -> Self {
-        let result : Vec<Statement> = pair.into_inner().map(|p| interpret_expression(p.into_inner())).collect();
+impl Interpret for Compound { 
+    fn interpret(pair : Pair<AstRule>, context : &mut Context) -> Self {
+        let whole = pair.as_span();
+        let mut pairsinner = pair.into_inner();
+        let name = match next(&mut pairsinner, "a name", whole) {
+            Ok(name) => Atom::interpret(name, context),
+            Err(e) => { context.error(e); Atom::default() },
+        };
+        let arguments : Vec<Statement> = pairsinner.map(|arg| interpret_expression(arg.into_inner(), context)).collect();
+        return Compound { name : name, arguments : Sequence::new(arguments) };
+    }
+}
+
+impl Interpret for Sequence { 
+    fn interpret(pair : Pair<AstRule>, context : &mut Context) -> Self {
+        let result : Vec<Statement> = pair.into_inner().map(|p| interpret_item(p, context)).collect();
         return Sequence::new(result);
     }
 }
 
+impl Interpret for Object { 
+    fn interpret(pair : Pair<AstRule>, context : &mut Context) -> Self {
+        let result : Vec<Statement> = pair.into_inner().map(|p| match p.as_rule() {
+            AstRule::obj_arg => Sequence::interpret(p, context).as_statement(), // (key, value)
+            _ => interpret_item(p, context),
+        }).collect();
+        return Object::new(Sequence::new(result), false);
+    }
+}
+
+// an item of a collection, either an expression or a spread capture (e.g. `*REST`)
+fn interpret_item(pair : Pair<AstRule>, context : &mut Context) -> Statement {
+    return match pair.as_rule() {
+        AstRule::spread => {
+            let whole = pair.as_span();
+            match next(&mut pair.into_inner(), "a variable", whole) {
+                Ok(variable) => Statement::Spread(Box::new(Variable::interpret(variable, context).as_statement())),
+                Err(e) => { context.error(e); Statement::Empty },
+            }
+        },
+        _ => interpret_expression(pair.into_inner(), context),
+    };
+}
 
-impl From<Pair<'_, AstRule>> for  Variable { 
-    fn from(pair : Pair<AstRule>) -> Self {
-        let name = pair.as_str().to_string();
-        return Variable { name : name};
+// a call of a registered function, the function must exist and be given the right number of arguments.
+fn interpret_call(pair : Pair<AstRule>, context : &mut Context) -> Result<Call, TeleoraError> {
+    let whole = pair.as_span();
+    let span = Span::from(whole);
+    let mut inner = pair.into_inner();
+    let name = next(&mut inner, "the name of a function", whole)?.as_str();
+    let arguments : Vec<Statement> = inner.map(|x| interpret_expression(x.into_inner(), context)).collect();
+    let Some(function) = context.registry.get_function(name) else {
+        return Err(TeleoraError::UnknownFunction { span : span, name : name.to_string(), known : context.registry.function_names() });
+    };
+    if arguments.len() != function.arity {
+        return Err(TeleoraError::WrongArity { span : span, name : name.to_string(), expected : function.arity, found : arguments.len() });
     }
+    return Ok(Call { function : function.clone(), arguments : Sequence::new(arguments) });
 }
 
-impl From<Pair<'_, AstRule>> for  Atom { 
-    fn from(pair : Pair<AstRule>) -> Self {
-        let name = pair.as_str().to_string();
-        return Atom(name);
+// the next of `pairs`, which the grammar ensures is there. It is missing if the pairs were not produced by the grammar, 
+// `expected` is then reported as missing from the pair at `span`.
+fn next<'i>(pairs : &mut Pairs<'i, AstRule>, expected : &str, span : pest::Span) -> Result<Pair<'i, AstRule>, TeleoraError> {
+    return pairs.next().ok_or_else(|| TeleoraError::Unexpected { span : span.into(), expected : expected.to_string(), found : "nothing".to_string() });
+}
+
+// an error for a pair that the grammar should not have produced where it was found.
+pub(crate) fn unexpected(expected : &str, found : AstRule, span : pest::Span) -> TeleoraError {
+    return TeleoraError::Unexpected { span : span.into(), expected : expected.to_string(), found : format!("{:?}", found) };
+}
+
+// the doc comment of a goal or a rule (its `///` lines without the `///`), where the goal or rule is written without it and
+// the pairs that follow it.
+fn documented(pair : Pair<AstRule>) -> (Option<String>, pest::Span, Pairs<AstRule>) {
+    let whole = pair.as_span();
+    let mut inner = pair.into_inner();
+    let doc = match inner.peek() {
+        Some(x) if x.as_rule() == AstRule::doc_comment => inner.next(),
+        _ => None,
+    };
+    let start = inner.peek().map_or(whole.start(), |x| x.as_span().start());
+    let span = pest::Span::new(whole.get_input(), start, whole.end()).unwrap_or(whole);
+    let doc = doc.map(|x| x.into_inner().map(|line| {
+        let text = line.as_str()["///".len()..].trim_end();
+        text.strip_prefix(' ').unwrap_or(text)
+    }).join("\n"));
+    return (doc, span, inner);
+}
+
+// where the tokens of `pair` are written, without the whitespace and comments that may follow them (e.g. after a
+// variable, in case it is followed by a postfix operator).
+fn tokens(pair : &Pair<AstRule>) -> Span {
+    let mut span = Span::from(pair.as_span());
+    if let Some(last) = pair.clone().into_inner().last() {
+        span.end = tokens(&last).end;
+    }
+    // the literal tokens that follow the last inner pair, e.g. `)`
+    if let Some(end) = last_token(&pair.get_input()[span.end..pair.as_span().end()]) {
+        span.end += end;
+    }
+    return span;
+}
+
+// where the parenthesised expression `pair` is written, with its parentheses (those that only whitespace and comments
+// separate from it).
+fn parenthesised(pair : &Pair<AstRule>) -> Span {
+    let (input, span) = (pair.get_input(), tokens(pair));
+    let (before, after) = (&input[..span.start], &input[span.end..]);
+    let start = before.match_indices('(').rev().map(|(i, _)| i).find(|i| last_token(&before[i + 1..]).is_none());
+    let end = after.match_indices(')').map(|(i, _)| span.end + i + 1).find(|i| last_token(&input[span.end..i - 1]).is_none());
+    return match (start, end) {
+        (Some(start), Some(end)) => pest::Span::new(input, start, end).map_or(span, Span::from),
+        _ => span,
+    };
+}
+
+// the end of the last token in `text`, i.e. of what is not whitespace or a comment, if there is one.
+fn last_token(text : &str) -> Option<usize> {
+    let mut end = None;
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        i += match comment(rest) {
+            0 => {
+                let c = rest.chars().next().unwrap_or(' ');
+                if !c.is_whitespace() {
+                    end = Some(i + c.len_utf8());
+                }
+                c.len_utf8()
+            },
+            n => n,
+        };
     }
+    return end;
 }
 
+// the syntax error in text that was skipped while parsing (see `Program::parse_recovering`), it is found by parsing the text
+// again on its own as `rule`.
+pub(crate) fn skipped(pair : Pair<AstRule>, rule : AstRule, registry : &Registry) -> TeleoraError {
+    let origin = Span::from(pair.as_span());
+    let text = pair.as_str().trim_end();
+    return match TeleoraParser::parse(rule, text) {
+        Err(e) => unknown_operator(TeleoraError::syntax(e, text), text, rule, registry).within(&origin),
+        Ok(_) => TeleoraError::Syntax { span : origin, expected : Vec::new(), unexpected : Vec::new() },
+    };
+}
 
-impl From<Pair<'_, AstRule>> for  Integer { 
-    fn from(pair : Pair<AstRule>) -> Self {
-        let value = pair.as_str().parse::<i32>().unwrap();
-        return Integer(value);
+// operators are registered when the program is loaded rather than when the grammar is built, so any name after an operand
+// is parsed as an infix operator (e.g. `A dot B`) and a missing `->` (e.g. in `X > 1 move`) is found after the name that
+// follows it. Unless the name is a registered operator the error is the one found without it.
+fn unknown_operator(error : TeleoraError, text : &str, rule : AstRule, registry : &Registry) -> TeleoraError {
+    let TeleoraError::Syntax { span, expected, .. } = &error else {
+        return error;
+    };
+    let before = text[..span.start].trim_end();
+    let start = before.rfind(|x : char| !(x.is_ascii_alphanumeric() || x == '_' || x == ':')).map_or(0, |i| i + 1);
+    let name = &before[start..];
+    let keyword = ["in", "union", "intersect", "minus"].contains(&name);
+    if !expected.iter().any(|x| x == "an expression") || !name.starts_with(|x : char| x.is_ascii_lowercase()) || keyword || registry.get_operator(name).is_some() {
+        return error;
+    }
+    let Err(e) = TeleoraParser::parse(rule, &text[..start]) else {
+        return error;
+    };
+    // the text before the name must be parsed up to its end, otherwise its own error comes first
+    return match TeleoraError::syntax(e, &text[..start]) {
+        TeleoraError::Syntax { span, expected, unexpected } if span.start >= text[..start].trim_end().len() => {
+            let (line, column) = pest::Position::new(text, start).map_or((span.line, span.column), |x| x.line_col());
+            TeleoraError::Syntax { span : Span { file : None, start : start, end : start + name.len(), line : line, column : column }, expected : expected, unexpected : unexpected }
+        },
+        _ => error,
+    };
+}
+
+impl Interpret for Variable { 
+    fn interpret(pair : Pair<AstRule>, context : &mut Context) -> Self {
+        return context.variable(pair.as_str(), Span::from(pair.as_span()));
+    }
+}
+
+impl Interpret for Atom { 
+    fn interpret(pair : Pair<AstRule>, context : &mut Context) -> Self {
+        return context.atom(pair.as_str());
+    }
+}
+
+
+impl TryFrom<Pair<'_, AstRule>> for  Integer { 
+    type Error = TeleoraError;
+    fn try_from(pair : Pair<AstRule>) -> Result<Self, TeleoraError> {
+        let literal = pair.as_str().replace('_', "");
+        let value = match literal.split_once("0x") {
+            Some((sign, digits)) => IntegerValue::from_str_radix(&format!("{}{}", sign, digits), 16),
+            None => literal.parse::<IntegerValue>(),
+        };
+        return match value {
+            Ok(value) => Ok(Integer(value)),
+            Err(_) => Err(invalid(&pair, format!("the integer is too large, integers must be between {} and {}", IntegerValue::MIN, IntegerValue::MAX))),
+        };
     }
 }
 
 
-impl From<Pair<'_, AstRule>> for  Float { 
+impl TryFrom<Pair<'_, AstRule>> for  Text { 
+    type Error = TeleoraError;
+    // removes the quotes and replaces each escape sequence with the character that it stands for.
+    fn try_from(pair : Pair<AstRule>) -> Result<Self, TeleoraError> {
+        let literal = pair.as_str();
+        let mut text = String::with_capacity(literal.len());
+        let mut chars = literal[1..literal.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                text.push(c);
+                continue;
+            }
+            let escaped = match chars.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some('u') => {
+                    let code : String = chars.by_ref().skip(1).take_while(|x| *x != '}').collect(); // the grammar ensures `{hex}`
+                    match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                        Some(x) => x,
+                        None => return Err(invalid(&pair, format!("`\\u{{{}}}` is not a valid character", code))),
+                    }
+                },
+                Some(x) => x, // `\"` or `\\`
+                None => unreachable!("The grammar does not allow a string to end with `\\`."),
+            };
+            text.push(escaped);
+        }
+        return Ok(Text(text));
+    }
+}
+
+impl From<Pair<'_, AstRule>> for  Boolean { 
     fn from(pair : Pair<AstRule>) -> Self {
-        let value = pair.as_str().parse::<f32>().unwrap();
-        return Float(value);
+        return Boolean(pair.as_str() == "true");
     }
 }
 
+impl TryFrom<Pair<'_, AstRule>> for  Float { 
+    type Error = TeleoraError;
+    fn try_from(pair : Pair<AstRule>) -> Result<Self, TeleoraError> {
+        let Ok(value) = pair.as_str().replace('_', "").parse::<FloatValue>() else {
+            return Err(invalid(&pair, "it is not a valid float".to_string()));
+        };
+        // floats that are too large to represent are parsed as infinite
+        if value.is_infinite() {
+            return Err(invalid(&pair, format!("the float is too large, floats must be between {:e} and {:e}", FloatValue::MIN, FloatValue::MAX)));
+        }
+        return Ok(Float(value));
+    }
+}
+
+// an error for a literal that follows the grammar but does not stand for a value.
+fn invalid(pair : &Pair<AstRule>, reason : String) -> TeleoraError {
+    return TeleoraError::InvalidLiteral { span : pair.as_span().into(), literal : pair.as_str().to_string(), reason : reason };
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // the expression written in `source` and the names of its atoms and variables.
+    pub(crate) fn expression(source : &str) -> (Statement, Symbols) {
+        let (mut statements, symbols) = expressions(&[source]);
+        return (statements.remove(0), symbols);
+    }
+
+    // the expressions written in `sources`, variables with the same name are the same variable (as if in one goal).
+    pub(crate) fn expressions(sources : &[&str]) -> (Vec<Statement>, Symbols) {
+        let mut symbols = Symbols::new();
+        let registry = Registry::default();
+        let mut context = Context::new(&mut symbols, &registry);
+        let statements = sources.iter().map(|source| {
+            let pair = TeleoraParser::parse(AstRule::expr, source).unwrap().next().unwrap();
+            return interpret_expression(pair.into_inner(), &mut context);
+        }).collect();
+        context.result().unwrap();
+        return (statements, symbols);
+    }
+
+    // the action that `source` produces for `observation`, as it is displayed.
+    pub(crate) fn run(source : &str, observation : &str) -> Result<String, TeleoraError> {
+        return run_with(source, observation, Numbers::default());
+    }
+
+    // the action that the program in `source` chooses for `observation`, numbers are compared and divided according to `numbers`.
+    pub(crate) fn run_with<N : Into<Numbers>>(source : &str, observation : &str, numbers : N) -> Result<String, TeleoraError> {
+        let mut program = Program::parse(source)?;
+        program.set_numbers(numbers.into());
+        let input = program.parse_observation(observation)?;
+        let action = program.evaluate(input)?;
+        return Ok(program.symbols().display(&action.0).to_string());
+    }
+
+    #[test]
+    fn action_that_is_not_an_action() {
+        let error = run("g(X) : true -> X", "3").unwrap_err();
+        let TeleoraError::Evaluation { error, span : Some(span), .. } = error else { panic!("{:?}", error) };
+        assert!(matches!(*error, EvaluationError::NotAction(Statement::Integer(Integer(3)))));
+        assert_eq!((span.start, span.end), (15, 16));
+    }
+
+    #[test]
+    fn boolean_conditions() {
+        assert_eq!(run("g(_) : { false -> a, true -> b }", "0").unwrap(), "b");
+        // a boolean variable is a condition, observations may hold booleans
+        let source = "g(Door_open) : { Door_open -> go, true -> wait }";
+        assert_eq!(run(source, "true").unwrap(), "go");
+        assert_eq!(run(source, "false").unwrap(), "wait");
+        assert_eq!(run("g(X) : X = [true, false] -> same", "[true, false]").unwrap(), "same");
+        assert_eq!(run("g(X) : !X && X = false -> negated", "false").unwrap(), "negated");
+        // `true` is a literal rather than an atom
+        assert_eq!(run("g(X) : { X = true -> boolean, true -> other }", "true").unwrap(), "boolean");
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        // operators of the same precedence are left associative
+        assert_eq!(run("g(A, B, C) : X = A - B - C -> r(X)", "10, 3, 2").unwrap(), "r(5)");
+        assert_eq!(run("g(A, B, C) : X = A - (B - C) -> r(X)", "10, 3, 2").unwrap(), "r(9)");
+        assert_eq!(run("g(A, B, C) : X = A / B * C -> r(X)", "12, 3, 2").unwrap(), "r(8)");
+        // prefix operators bind more tightly than binary ones, and less tightly than indexing
+        let (statements, _) = expressions(&["-X * Y", "-P[0]", "!A && B || C"]);
+        assert!(matches!(&statements[0], Statement::BinaryOperator(BinaryOperator::Multiply(x, _)) if matches!(**x, Statement::UnaryOperator(UnaryOperator::Minus(_)))));
+        assert!(matches!(&statements[1], Statement::UnaryOperator(UnaryOperator::Minus(x)) if matches!(**x, Statement::BinaryOperator(BinaryOperator::Index(_, _)))));
+        assert_eq!(run("g(X, Y) : Z = -X * Y -> r(Z)", "2, 3").unwrap(), "r(-6)");
+        // `!` binds more tightly than `&&`, which binds more tightly than `||`
+        let Statement::BinaryOperator(BinaryOperator::Or(x, _)) = &statements[2] else { panic!("{:?}", statements[2]) };
+        assert!(matches!(&**x, Statement::BinaryOperator(BinaryOperator::And(x, _)) if matches!(**x, Statement::UnaryOperator(UnaryOperator::Not(_)))));
+        let source = "g(A, B, C) : { (!A && B || C) -> yes, true -> no }";
+        assert_eq!(run(source, "true, false, true").unwrap(), "yes");
+        assert_eq!(run(source, "true, true, false").unwrap(), "no");
+        assert_eq!(run(source, "false, true, false").unwrap(), "yes");
+        // comparisons bind more tightly than `&&`
+        assert_eq!(run("g(X, Y) : X > 1 && Y < 2 -> yes", "2, 1").unwrap(), "yes");
+    }
+
+    #[test]
+    fn alternative_conditions() {
+        // `||` between conditions separates alternatives, as `;` does
+        let (semicolon, bars) = (Program::parse("g(X) : X > 1; X < 0 -> out").unwrap(), Program::parse("g(X) : X > 1 || X < 0 -> out").unwrap());
+        assert_eq!(semicolon, bars);
+        assert_eq!(semicolon.goals[0].1[0].body[0].alternatives.len(), 2);
+        for source in ["g(X) : { X > 1; X < 0 -> out, true -> in }", "g(X) : { X > 1 || X < 0 -> out, true -> in }", "g(X) : { (X > 1 || X < 0) -> out, true -> in }"] {
+            let actions : Vec<String> = ["2", "-1", "0"].iter().map(|x| run(source, x).unwrap()).collect();
+            assert_eq!(actions, ["out", "out", "in"], "{}", source);
+        }
+        // a parenthesised disjunction is one condition
+        let parenthesised = Program::parse("g(X) : (X > 1 || X < 0), X != 5 -> out").unwrap();
+        let alternatives = &parenthesised.goals[0].1[0].body[0].alternatives;
+        assert_eq!((alternatives.len(), alternatives[0].len()), (1, 2));
+        assert!(matches!(alternatives[0][0].0, Statement::BinaryOperator(BinaryOperator::Or(_, _))));
+        // each alternative binds its own variables
+        assert_eq!(run("g(P) : { Y = P[0], Y > 1; Y = P[1], Y > 1 -> r(Y), true -> none }", "[0, 5]").unwrap(), "r(5)");
+    }
+
+    #[test]
+    fn conditions_must_be_boolean() {
+        for (source, observation) in [("g(_) : 1 -> a", "0"), ("g(X) : X -> a", "yes"), ("g(X) : X + 1 -> a", "1"), ("g(X) : X && true -> a", "[1]")] {
+            let error = run(source, observation).unwrap_err();
+            assert_eq!(error.code(), "E0014", "{}", source);
+            let TeleoraError::Evaluation { error, .. } = error else { unreachable!() };
+            assert!(matches!(*error, EvaluationError::NotBoolean(_) | EvaluationError::TypeMismatch { .. }), "{}: {:?}", source, error);
+        }
+        // the example that the command line runs
+        assert_eq!(run(include_str!("../test/test1.tela"), include_str!("../test/observation.tela")).unwrap(), "a1");
+    }
 
+    #[test]
+    fn arithmetic_in_the_head() {
+        let countdown = "countdown(N + 1) : true -> next(N)\ncountdown(_) : true -> done";
+        assert_eq!(run(countdown, "5").unwrap(), "next(4)");
+        assert_eq!(run(countdown, "0.5").unwrap(), "next(-0.5)");
+        let even = "half(2 * N) : true -> halved(N)\nhalf(_) : true -> odd";
+        assert_eq!(run(even, "6").unwrap(), "halved(3)");
+        assert_eq!(run(even, "7").unwrap(), "odd");
+        assert_eq!(run("g(1 - N) : true -> a(N)", "3").unwrap(), "a(-2)");
+        // once its variable is bound the expression is evaluated
+        assert_eq!(run("g(N, N + 1) : true -> a(N)", "2, 3").unwrap(), "a(2)");
+        assert_eq!(run("g(N, N + 1) : true -> a(N)\ng(_, _) : true -> b", "2, 4").unwrap(), "b");
+    }
 
+    #[test]
+    fn index_in_an_observation() {
+        assert_eq!(run("g(X) : X + 1 > 2 -> yes", "[1, 2][1]").unwrap(), "yes");
+        let error = run("g(X) : X + 1 > 2 -> yes", "[1, 2][2]").unwrap_err();
+        assert!(matches!(error, TeleoraError::Evaluation { ref error, .. } if matches!(**error, EvaluationError::NoElement(_))), "{:?}", error);
+    }
 
+    #[test]
+    fn conditions_in_any_order() {
+        assert_eq!(run("g(_) : { P[0] > 1, P = [2, 3] -> yes, true -> no }", "0").unwrap(), "yes");
+        assert_eq!(run("g(_) : { P = [2, 3], P[0] > 1 -> yes, true -> no }", "0").unwrap(), "yes");
+        // a collection that no condition binds is reported when the program is loaded
+        assert!(matches!(run("g(_) : { P[0] > 1 -> yes, true -> no }", "0"), Err(TeleoraError::UnboundVariable { .. })));
+    }
+
+    #[test]
+    fn evaluated_equality_follows_the_policy() {
+        let source = "g(X) : { !(X = 1.0) -> different, true -> same }";
+        assert_eq!(run(source, "1.0001").unwrap(), "different");
+        assert_eq!(run_with(source, "1.0001", NumericEquality::tolerant(1e-3, 0.0)).unwrap(), "same");
+        assert_eq!(run_with(source, "1", NumericEquality::strict()).unwrap(), "different");
+        let source = "g(X) : { X != 1.0 -> different, true -> same }";
+        assert_eq!(run_with(source, "1.0001", NumericEquality::tolerant(1e-3, 0.0)).unwrap(), "same");
+        let source = "g(X) : { X != {1, 2} -> different, true -> same }";
+        assert_eq!(run(source, "{2, 1}").unwrap(), "same");
+    }
+
+    #[test]
+    fn each_program_divides_as_it_chooses() {
+        let source = "g(X) : { X / 2 > 1 -> more, true -> less }";
+        assert_eq!(run(source, "3").unwrap(), "less");
+        assert_eq!(run_with(source, "3", Numbers::rational()).unwrap(), "more");
+        // a constant division in a head is evaluated when it is matched, by the program's choice
+        assert_eq!(run_with("g(N + 1 / 2) : true -> a(N)", "1", Numbers::rational()).unwrap(), "a(1/2)");
+        assert_eq!(run("g(N + 1 / 2) : true -> a(N)", "1").unwrap(), "a(1)");
+    }
 
+    #[test]
+    fn large_integers_compared_with_floats() {
+        let source = "g(X) : { X > 9007199254740992.0 -> larger, X = 9007199254740992.0 -> same, true -> smaller }";
+        assert_eq!(run(source, "9007199254740993").unwrap(), "larger");
+        assert_eq!(run(source, "9007199254740992").unwrap(), "same");
+    }
+
+    #[test]
+    fn missing_arrow_before_an_action() {
+        // `move` is not an operator, so the `->` before it is missing
+        let (_, errors) = Program::parse_recovering("g(X) : X > 1 move\nh(X) : { X > 1 stop, true -> b }", Registry::default());
+        let spans : Vec<(usize, usize, &str)> = errors.iter().map(|x| match x {
+            TeleoraError::Syntax { span, expected, .. } => (span.start, span.end, expected[0].as_str()),
+            x => panic!("{:?}", x),
+        }).collect();
+        assert_eq!(spans, [(13, 17, "`->`"), (33, 37, "`->`")]);
+        // a registered operator is missing its right operand
+        let mut registry = Registry::default();
+        registry.operator("dot", 0, |x, _| Ok(x.clone())).unwrap();
+        let (_, errors) = Program::parse_recovering("g(X) : X > 1 dot", registry);
+        assert!(matches!(errors.as_slice(), [TeleoraError::Syntax { expected, .. }] if expected[0] == "an expression"), "{:?}", errors);
+    }
+
+    #[test]
+    fn operators_of_the_host() {
+        let integer = |x : &Statement| match x { Statement::Integer(x) => Ok(x.0), x => Err(EvaluationError::TypeMismatch { operator : "integer", operands : vec![x.clone()] }) };
+        let mut registry = Registry::default();
+        let names = ["less".to_string(), "times".to_string()]; // names need not be known at compile time
+        registry.operator(names[0].clone(), 1, move |x, y| Ok((integer(x)? - integer(y)?).as_statement())).unwrap();
+        registry.operator(names[1].as_str(), 2, move |x, y| Ok((integer(x)? * integer(y)?).as_statement())).unwrap();
+        let cases = [
+            ("10 less 2 times 3", "10 less 2 times 3", "4"),
+            ("2 times 3 less 1", "2 times 3 less 1", "5"),
+            ("10 less 2 less 3", "10 less 2 less 3", "5"),
+            ("10 less (2 less 3)", "10 less (2 less 3)", "11"),
+            ("(10 less 2) times 3", "(10 less 2) times 3", "24"),
+            ("1 less 2 times 3 times 2 less 4", "1 less 2 times 3 times 2 less 4", "-15"),
+            ("20 less 2 times 3 less 1 + 1", "20 less 2 times 3 less 1 + 1", "12"),
+        ];
+        for (source, written, value) in cases {
+            let mut program = Program::parse_with_registry(&format!("g(_) : Y = {} -> a(Y)", source), registry.clone()).unwrap();
+            let input = program.parse_observation("0").unwrap();
+            let action = program.evaluate(input).unwrap();
+            let condition = &program.goals[0].1[0].body[0].alternatives[0][0].0;
+            assert_eq!(program.symbols().display(&action.0).to_string(), format!("a({})", value), "{}", source);
+            assert_eq!(program.symbols().display(condition).to_string(), format!("Y = {}", written), "{}", source);
+        }
+        assert!(matches!(Registry::default().operator("in".to_string(), 0, |x, _| Ok(x.clone())), Err(TeleoraError::InvalidName { .. })));
+    }
+
+    #[test]
+    fn numeric_literals() {
+        let sources = ["42", "1_000_000", "0xff", "-0x1_0", "9223372036854775807", "-9223372036854775808", "1.5", "12e3", "1.5e-3", "-2_5.0e2"];
+        let (statements, symbols) = expressions(&sources);
+        let values : Vec<String> = statements.iter().map(|x| symbols.display(x).to_string()).collect();
+        assert_eq!(values, ["42", "1000000", "255", "-16", "9223372036854775807", "-9223372036854775808", "1.5", "12000.0", "0.0015", "-2500.0"]);
+    }
+
+    #[test]
+    fn string_literals() {
+        let sources = [r#""Traffic Light""#, r#""a\"b\\c""#, r#""line\nnext\ttab""#, r#""\u{e9}t\u{1F6A6}""#, r#""""#];
+        let (statements, _) = expressions(&sources);
+        let texts : Vec<&str> = statements.iter().map(|x| match x { Statement::Text(y) => y.0.as_str(), x => panic!("{:?}", x) }).collect();
+        assert_eq!(texts, ["Traffic Light", "a\"b\\c", "line\nnext\ttab", "\u{e9}t\u{1F6A6}", ""]);
+        // strings are matched and compared by their text
+        assert_eq!(run(r#"g(X) : X = "Traffic Light" -> stop"#, r#""Traffic Light""#).unwrap(), "stop");
+        assert_eq!(run(r#"g(X, Y) : { X < Y -> before, true -> after }"#, r#""apple", "banana""#).unwrap(), "before");
+        // an escape that is not a character is reported where it is written
+        let error = Program::parse(r#"g(X) : X = "\u{D800}" -> a"#).unwrap_err();
+        assert!(matches!(error, TeleoraError::InvalidLiteral { ref literal, .. } if literal == r#""\u{D800}""#), "{:?}", error);
+    }
+
+    #[test]
+    fn literals_out_of_range() {
+        for (source, literal) in [("g(X) : X > 9223372036854775808 -> a", "9223372036854775808"), ("g(X) : X > 0x1_0000_0000_0000_0000 -> a", "0x1_0000_0000_0000_0000"), ("g(X) : X < 1e999 -> a", "1e999")] {
+            let error = Program::parse(source).unwrap_err();
+            let TeleoraError::InvalidLiteral { span, literal : found, .. } = error else { panic!("{:?}", error) };
+            assert_eq!((&source[span.start..span.end], found.as_str()), (literal, literal));
+        }
+    }
+
+    #[test]
+    fn runtime_errors_at_their_condition() {
+        let source = "g(X) : {\n    X > 1, X + a > 2 -> a,\n    true -> b\n}";
+        let error = run(source, "3").unwrap_err();
+        let TeleoraError::Evaluation { span : Some(span), .. } = &error else { panic!("{:?}", error) };
+        assert_eq!((&source[span.start..span.end], span.line, span.column), ("X + a", 2, 12));
+        // in a subgoal the error is at its expression, the goals that called it are traced
+        let source = "g(X) : true -> h(X)\nh(X) : X + a > 2 -> a";
+        let error = run(source, "3").unwrap_err();
+        let TeleoraError::Evaluation { span : Some(span), trace, .. } = &error else { panic!("{:?}", error) };
+        assert_eq!((&source[span.start..span.end], trace.len()), ("X + a", 1));
+        // the expression is told from the values in the error
+        let at = |source : &str, observation : &str| {
+            let error = run(source, observation).unwrap_err();
+            let TeleoraError::Evaluation { span : Some(span), .. } = &error else { panic!("{:?}", error) };
+            return source[span.start..span.end].to_string();
+        };
+        assert_eq!(at("g(X, Y) : X / Y + 1 > X / 2 -> a", "4, 0"), "X / Y");
+        assert_eq!(at("g(X) : len(X) + (X * 2) > 1 -> a", "a"), "len(X)");
+        assert_eq!(at("g(X) : X.a + 1 > 1 -> a", "{a : b}"), "X.a + 1");
+        assert_eq!(at("g(X) : -(X) > 1 -> a", "a"), "-(X)");
+        assert_eq!(at("g(X) : X + 1 > 1 -> r(X / 0)", "2"), "X / 0");
+        // a condition ends at its last token, not at the whitespace and comments after it
+        assert_eq!(at("g(X) : X -> yes", "1"), "X");
+        assert_eq!(at("g(X) : X  /* a */ -> yes", "1"), "X");
+        assert_eq!(at("g(X) : (X)\n  // a\n  -> yes", "1"), "(X)");
+    }
+
+    #[test]
+    fn doc_comments() {
+        let docs = |source : &str| {
+            let program = Program::parse(source).unwrap();
+            return program.goals().map(|goal| (goal.doc().map(str::to_string), goal.body().iter().map(|x| x.doc().map(str::to_string)).collect::<Vec<_>>())).collect::<Vec<_>>();
+        };
+        let doc = |x : &str| Some(x.to_string());
+        // a goal and the rules of a long goal, the lines are joined
+        let source = "/// Moves.\n///  Twice.\nmove(X) : {\n    /// Ahead.\n    X > 1 -> ahead,\n    // not a doc comment\n    true -> stop\n}\n";
+        assert_eq!(docs(source), [(doc("Moves.\n Twice."), vec![doc("Ahead."), None])]);
+        // `///` inside a block comment, even a nested one, documents nothing
+        let source = "/* outer /* inner */\n/// not a doc comment */\ng(X) : X > 1 -> a\n/*\n/// nor this\n*/ h(X) : X > 1 -> b";
+        assert_eq!(docs(source), [(None, vec![None]), (None, vec![None])]);
+        // `////`, a blank line or a comment after the last goal end the doc comment
+        let source = "//// Not a doc comment.\ng(X) : X > 1 -> a\n/// Not either.\n\nh(X) : X > 1 -> b\n/// The last line.";
+        assert_eq!(docs(source), [(None, vec![None]), (None, vec![None])]);
+        let source = "g(X) : X > 1 -> a // the last line";
+        assert_eq!(docs(source), [(None, vec![None])]);
+        // `///` after a condition is a comment
+        let source = "g(X) : X > 1 /// a comment\n-> a";
+        assert_eq!(docs(source), [(None, vec![None])]);
+        // the goal and its rules are written after their doc comments
+        let source = "/// G.\ng(X) : {\n    /// A.\n    X > 1 -> a\n}";
+        let program = Program::parse(source).unwrap();
+        let goal = program.goals().next().unwrap();
+        assert_eq!((goal.span().line, goal.body()[0].span().line), (2, 4));
+    }
+
+    #[test]
+    fn recovers_from_syntax_errors() {
+        let source = "a(X) : X > 1 -> yes\nb(X) X > 1 -> no\nc(X) : {\n    X > 1 -> (,\n    X < 0 -> c,\n    X = [1 -> d\n}\nd(X) : true -> yes\n";
+        let (program, errors) = Program::parse_recovering(source, Registry::default());
+        // each independent error is found, the goals without errors are loaded
+        let lines : Vec<usize> = errors.iter().map(|x| match x {
+            TeleoraError::Syntax { span, .. } => span.line,
+            x => panic!("{:?}", x),
+        }).collect();
+        assert_eq!(lines, [2, 4, 6]);
+        let names : Vec<&str> = program.goals().map(|x| program.symbols().name(x.get_name().0)).collect();
+        assert_eq!(names, ["a", "d"]);
+        // without recovery only the first error is reported
+        assert!(matches!(Program::parse(source), Err(TeleoraError::Syntax { span, .. }) if span.line == 2));
+    }
+
+    #[test]
+    fn every_error_of_a_goal() {
+        let (registry, mut symbols) = (Registry::default(), Symbols::new());
+        let source = "g(X) : { lne(X) > 1 -> a, X > 1 -> 2 ++ b, lem(X) > 2 -> c }";
+        let document = TeleoraParser::parse(AstRule::document, source).unwrap().next().unwrap();
+        let goal = document.into_inner().find(|x| x.as_rule() == AstRule::goal).unwrap();
+        let errors = Goal::new(goal, &mut symbols, &registry).unwrap_err();
+        let codes : Vec<&str> = errors.iter().map(|x| x.code()).collect();
+        assert_eq!(codes, ["E0003", "E0001", "E0003"]);
+        assert!(matches!(&errors[2], TeleoraError::UnknownFunction { name, .. } if name == "lem"), "{:?}", errors[2]);
+    }
+
+    #[test]
+    fn pairs_of_the_wrong_kind() {
+        // pairs that the grammar would not produce where they are given are reported rather than panicking
+        let (registry, mut symbols) = (Registry::default(), Symbols::new());
+        let atom = TeleoraParser::parse(AstRule::atom, "g").unwrap().next().unwrap();
+        let errors = Goal::new(atom, &mut symbols, &registry).unwrap_err();
+        assert!(matches!(errors.as_slice(), [TeleoraError::Unexpected { expected, found, .. }] if expected == "a head" && found == "nothing"), "{:?}", errors);
+        let mut context = Context::new(&mut symbols, &registry);
+        let integer = TeleoraParser::parse(AstRule::signed_integer, "1").unwrap().next().unwrap();
+        Action::interpret(integer, &mut context);
+        let atom = TeleoraParser::parse(AstRule::atom, "a").unwrap().next().unwrap();
+        Compound::interpret(atom, &mut context);
+        let errors = context.take_errors();
+        assert!(matches!(&errors[0], TeleoraError::Unexpected { expected, found, .. } if expected == "an action" && found == "signed_integer"), "{:?}", errors);
+        assert!(matches!(&errors[1], TeleoraError::Unexpected { expected, found, .. } if expected == "a name" && found == "nothing"), "{:?}", errors);
+        assert_eq!(errors[0].code(), errors[1].code());
+    }
+
+    // the program written in `source`, whose names are interned after those in `seen`.
+    fn interned_after(source : &str, seen : &[&str]) -> Program {
+        let mut symbols = Symbols::new();
+        seen.iter().for_each(|x| { symbols.intern(x); });
+        let registry = Registry::default();
+        let document = TeleoraParser::parse(AstRule::document, source).unwrap().next().unwrap();
+        let goals = document.into_inner().filter(|x| x.as_rule() == AstRule::goal).map(|x| Goal::new(x, &mut symbols, &registry).unwrap()).collect();
+        return Program::assemble(goals, symbols, registry, Vec::new()).0;
+    }
+
+    #[test]
+    fn programs_equal_whatever_the_order_of_their_symbols() {
+        let source = "g(X) : X > 1 -> a(b, X)\nh(_) : true -> c";
+        assert_eq!(interned_after(source, &[]), interned_after(source, &["c", "b", "X", "a"]));
+        assert_ne!(interned_after(source, &[]), interned_after("g(X) : X > 1 -> a(d, X)\nh(_) : true -> c", &["c", "b"]));
+        assert_ne!(interned_after(source, &["Y"]), interned_after("g(Y) : Y > 1 -> a(b, Y)\nh(_) : true -> c", &["X"]));
+    }
+
+    #[test]
+    fn each_program_has_its_own_symbols() {
+        let mut program = Program::parse("g(X) : true -> a(X)").unwrap();
+        let other = Program::parse("g(Y) : true -> b(Y)").unwrap();
+        assert_eq!((program.symbols().get("b"), other.symbols().get("a")), (None, None));
+        // names in an observation are interned by the program that it is given to
+        program.parse_observation("b, c").unwrap();
+        assert!(program.symbols().get("b").is_some() && program.symbols().get("c").is_some());
+        assert_eq!((other.symbols().get("b").is_some(), other.symbols().get("c")), (true, None));
+    }
+
+    #[test]
+    fn head_that_cannot_be_solved() {
+        for head in ["g(N * M)", "g(N / 2)", "g(len(S))", "g(N + N)"] {
+            let error = Program::parse(&format!("{} : true -> a", head)).unwrap_err();
+            assert!(matches!(error, TeleoraError::InvalidHead { .. }), "{}: {:?}", head, error);
+        }
+    }
+}
@@ -0,0 +1,25 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)] // explicit returns and field names are the preferred style.
+
+use pest_derive::Parser;
+
+#[macro_use]
+mod utils;
+
+pub mod error;
+pub mod diagnostic;
+pub mod interpret;
+pub mod analysis;
+pub mod registry;
+pub mod module;
+pub mod format;
+pub mod statement;
+
+pub use error::{TeleoraError, Span};
+pub use diagnostic::{Diagnostic, Severity};
+pub use interpret::Program;
+pub use registry::Registry;
+pub use module::Loader;
+
+#[derive(Parser)]
+#[grammar = "teleora.pest"]
+pub struct TeleoraParser;
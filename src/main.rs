@@ -1,43 +1,80 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)] // explicit returns and field names are the preferred style.
 
-use pest::Parser;
-use pest_derive::Parser;
-
-#[macro_use]
-mod utils;
-
-mod interpret;
-use interpret::{Program};
-
-mod statement;
-
-#[derive(Parser)]
-#[grammar = "teleora.pest"]
-pub struct TeleoraParser;
+use std::io::IsTerminal;
+use std::path::Path;
+use teleora::{Diagnostic, Registry, Loader};
 
 const TEST_PROGRAM:&str = include_str!("../test/test1.tela");
 const TEST_OBSERVATION:&str = include_str!("../test/observation.tela");
 
 fn main() {
+    // diagnostics are coloured on a terminal unless `NO_COLOR` is set (see https://no-color.org)
+    let colour = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let arguments : Vec<String> = std::env::args().skip(1).collect();
+    if arguments.first().is_some_and(|x| x == "fmt") {
+        std::process::exit(fmt(&arguments[1..], colour));
+    }
     println!("{}", TEST_PROGRAM);
     println!("{}", TEST_OBSERVATION);
-    match TeleoraParser::parse(Rule::document, TEST_PROGRAM) {
-        
-        Ok(pairs) => {
-            let program = Program::new(pairs);
-            if let Ok(observation) = TeleoraParser::parse(Rule::observation, TEST_OBSERVATION) {
-                //println!("Observation: {:?}", observation);
-                let sequence = Program::interpret_observation(observation).unwrap();
-                //println!("Observation: {:?}", sequence);
-                program.evaluate(sequence);
-            } else {
-                println!("Observation: {TEST_OBSERVATION} parse failed."); // TODO some info please.
-            }
-        }
-        Err(e) => { println!("Parse failed: {:?}", e);}
+    // every error in the program is reported, not only the first. Imported modules are found relative to the program.
+    let mut loader = Loader::default();
+    let (mut program, errors) = loader.load_source(TEST_PROGRAM, Some(Path::new("test/test1.tela")), Registry::default());
+    // a diagnostic is shown with the source of the file that it is in
+    let render = |diagnostic : &Diagnostic| {
+        let source = diagnostic.span.as_ref().and_then(|x| x.file.as_deref()).and_then(|x| loader.source(x)).unwrap_or(TEST_PROGRAM);
+        return diagnostic.render(source, colour);
+    };
+    for warning in program.warnings() {
+        println!("{}", render(warning));
+    }
+    for e in errors.iter() {
+        println!("{}", render(&Diagnostic::from(e)));
+    }
+    if !errors.is_empty() {
+        return;
+    }
+    match program.parse_observation(TEST_OBSERVATION).map_err(|e| e.in_file("test/observation.tela")) {
+        Ok(sequence) => match program.evaluate(sequence) {
+            Ok(action) => println!("Action: {}", program.symbols().display(&action.0)),
+            Err(e) => println!("{}", render(&Diagnostic::from(&e))),
+        },
+        Err(e) => println!("{}", Diagnostic::from(&e).render(TEST_OBSERVATION, colour)),
     }
 }
 
 
+// `teleora fmt [--check] FILE...` writes each file in the canonical layout. With `--check` the files are left as they are
+// and the command fails if any of them is not formatted, e.g. in continuous integration.
+fn fmt(arguments : &[String], colour : bool) -> i32 {
+    let check = arguments.iter().any(|x| x == "--check");
+    let mut status = 0;
+    for path in arguments.iter().filter(|x| *x != "--check") {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("error: cannot read {}: {}", path, e);
+                status = 2;
+                continue;
+            },
+        };
+        match teleora::format::format(&source, Some(Path::new(path)), Registry::default()) {
+            Ok(formatted) if formatted == source => (),
+            Ok(_) if check => {
+                println!("would reformat {}", path);
+                status = status.max(1);
+            },
+            Ok(formatted) => if let Err(e) = std::fs::write(path, formatted) {
+                eprintln!("error: cannot write {}: {}", path, e);
+                status = 2;
+            },
+            Err(e) => {
+                println!("{}", Diagnostic::from(&e.in_file(path.as_str())).render(&source, colour));
+                status = 2;
+            },
+        }
+    }
+    return status;
+}
 
 /*
 fn evaluate(entry_point : Goal, arguments : Vec<Statement> ) -> Option<Action> {
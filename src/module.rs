@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use pest::Parser;
+use pest::iterators::{Pair, Pairs};
+use crate::{Rule as AstRule, TeleoraParser};
+use crate::error::{TeleoraError, Span};
+use crate::interpret::{Program, Goal, skipped, unexpected};
+use crate::registry::Registry;
+use crate::statement::{Atom, Text, Symbols};
+
+// A program may be split into modules, one per file. A module imports others at the top of its file, either by path
+// (`import "nav.tela"`) or by name (`import nav` is found as `nav.tela` and `import a::b` as `a/b.tela`). The goals of an
+// imported module are called by their name qualified by the module (e.g. `nav::move`), those listed by the import (e.g.
+// `import nav::{move}`) may also be called by their name alone. A module with an export list (e.g. `export {move}`) only
+// makes the goals that it lists visible to other modules. Each module is loaded once however often it is imported, a
+// module may not (indirectly) import itself.
+
+/// Finds, reads and loads the modules of a program. An import is looked for relative to the directory of the importing
+/// file, then in each directory of the search path in order.
+#[derive(Debug, Clone, Default)]
+pub struct Loader {
+    search_path : Vec<PathBuf>,
+    sources : HashMap<Arc<str>, String>, // the text of each file that was loaded, by the name that spans give it
+}
+
+// a module that has been loaded.
+struct Module {
+    namespace : String,     // e.g. `nav`, its goals are called `nav::move` and so on
+    path : PathBuf,         // the canonical path of its file, a module is identified by it
+    span : Span,            // the import that first loaded it
+    exports : Vec<String>,  // the goals that other modules may call
+}
+
+// an import at the top of a module.
+struct Import {
+    span : Span,
+    path : PathBuf,                 // relative to the directory of the importing file or to the search path
+    namespace : String,
+    names : Vec<(String, Span)>,    // goals that may be called without qualification
+}
+
+// the state of loading the modules of a program.
+struct Loading<'r> {
+    symbols : Symbols,
+    registry : &'r Registry,
+    modules : Vec<Module>,          // the modules loaded so far
+    stack : Vec<(PathBuf, String)>, // the modules being loaded (and their names), each imports the next
+    goals : Vec<Goal>,              // the goals of the imported modules
+    errors : Vec<TeleoraError>,
+}
+
+impl Loader {
+    /// adds a directory to the end of the search path.
+    pub fn search<P : Into<PathBuf>>(&mut self, directory : P) -> &mut Self {
+        self.search_path.push(directory.into());
+        return self;
+    }
+
+    /// the text of a file that was loaded, `file` is its name as given by a span (see `Span::file`).
+    pub fn source(&self, file : &str) -> Option<&str> {
+        return self.sources.get(file).map(String::as_str);
+    }
+
+    /// reads and loads the program in the file at `path` and the modules that it imports, recovering from errors so that
+    /// all of them are found (see `Program::parse_recovering`).
+    pub fn load_file<P : AsRef<Path>>(&mut self, path : P, registry : Registry) -> (Program, Vec<TeleoraError>) {
+        let path = path.as_ref();
+        return match std::fs::read_to_string(path) {
+            Ok(source) => self.load_source(&source, Some(path), registry),
+            Err(e) => {
+                let error = TeleoraError::ModuleNotFound { span : Span::default(), module : path.display().to_string(), searched : vec![format!("{} ({})", path.display(), e)] };
+                Program::assemble(Vec::new(), Symbols::new(), registry, vec![error])
+            },
+        };
+    }
+
+    /// loads the program in `source` and the modules that it imports. If `path` is given then imports are looked for
+    /// relative to its directory and spans name it, otherwise they are looked for relative to the current directory.
+    pub fn load_source(&mut self, source : &str, path : Option<&Path>, registry : Registry) -> (Program, Vec<TeleoraError>) {
+        pest::set_error_detail(true); // so that syntax errors can describe the tokens that were expected
+        if let Some(path) = path {
+            self.sources.insert(Arc::from(path.display().to_string()), source.to_string());
+        }
+        return match TeleoraParser::parse(AstRule::document, source) {
+            Ok(document) => self.load_document(document, path, registry),
+            Err(e) => {
+                let error = TeleoraError::syntax(e, source);
+                let error = match path {
+                    Some(path) => error.in_file(&path.display().to_string()),
+                    None => error,
+                };
+                Program::assemble(Vec::new(), Symbols::new(), registry, vec![error])
+            },
+        };
+    }
+
+    /// loads a parsed program (a `document`) and the modules that it imports, see `load_source`.
+    pub fn load_document(&mut self, mut document : Pairs<AstRule>, path : Option<&Path>, registry : Registry) -> (Program, Vec<TeleoraError>) {
+        let document = match document.next() {
+            Some(pair) if pair.as_rule() == AstRule::document => pair,
+            Some(pair) => return Program::assemble(Vec::new(), Symbols::new(), registry, vec![unexpected("a document", pair.as_rule(), pair.as_span())]),
+            None => {
+                let error = TeleoraError::Unexpected { span : Span::default(), expected : "a document".to_string(), found : "nothing".to_string() };
+                return Program::assemble(Vec::new(), Symbols::new(), registry, vec![error]);
+            },
+        };
+        let mut loading = Loading { symbols : Symbols::new(), registry : &registry, modules : Vec::new(), stack : Vec::new(), goals : Vec::new(), errors : Vec::new() };
+        if let Some(path) = path {
+            let name = path.file_stem().map_or(String::new(), |x| x.to_string_lossy().to_string());
+            loading.stack.push((canonical(path), name));
+        }
+        // the goals of the program come before those of its modules, so that its first goal is evaluated by default
+        let mut goals = self.load_module(document, None, path, &mut loading);
+        goals.append(&mut loading.goals);
+        let Loading { symbols, errors, .. } = loading;
+        return Program::assemble(goals, symbols, registry, errors);
+    }
+
+    // loads the goals of a module (or of the program if `namespace` is `None`) and the modules that it imports, the goals
+    // of the imported modules are added to `loading`.
+    fn load_module(&mut self, document : Pair<AstRule>, namespace : Option<&str>, path : Option<&Path>, loading : &mut Loading) -> Vec<Goal> {
+        let file = path.map(|x| x.display().to_string());
+        let mut errors = Vec::new();
+        let mut imports = Vec::new();
+        let mut exports : Option<Vec<String>> = None;
+        let mut goals = Vec::new();
+        for pair in document.into_inner() {
+            match pair.as_rule() {
+                AstRule::import => match Import::interpret(pair) {
+                    Ok(import) => imports.push(match &file {
+                        Some(file) => import.in_file(file),
+                        None => import,
+                    }),
+                    Err(e) => errors.push(e),
+                },
+                AstRule::export => exports.get_or_insert_with(Vec::new).extend(names(pair).into_iter().map(|(x, _)| x)),
+                AstRule::goal => match Goal::new(pair, &mut loading.symbols, loading.registry) {
+                    Ok(mut goal) => {
+                        if let Some(file) = &file {
+                            goal.in_file(file);
+                        }
+                        goals.push(goal);
+                    },
+                    Err(e) => errors.extend(e),
+                },
+                AstRule::invalid => errors.push(skipped(pair, AstRule::goal_only, loading.registry)),
+                _ => (), // EOI
+            }
+        }
+        // the modules that this one imports, by their name
+        let directory = path.and_then(Path::parent).unwrap_or(Path::new(""));
+        let mut modules : HashMap<String, Vec<String>> = HashMap::new();
+        for import in imports.iter() {
+            if let Some(exports) = self.import(import, directory, loading, &mut errors) {
+                modules.insert(import.namespace.clone(), exports);
+            }
+        }
+        // the goals of this module are qualified by its name, as are those it calls by their name alone
+        let qualify = |name : &str| match namespace {
+            Some(namespace) => format!("{}::{}", namespace, name),
+            None => name.to_string(),
+        };
+        let local : HashMap<String, Span> = goals.iter().map(|x : &Goal| (loading.symbols.name(x.get_name().0).to_string(), x.span().clone())).collect();
+        let mut unqualified : HashMap<String, (String, Span)> = HashMap::new();
+        for import in imports.iter() {
+            let Some(exported) = modules.get(&import.namespace) else {
+                continue;
+            };
+            for (name, span) in import.names.iter() {
+                if !exported.contains(name) {
+                    errors.push(TeleoraError::NotExported { span : span.clone(), module : import.namespace.clone(), name : name.clone(), exported : exported.clone() });
+                } else if let Some(other) = local.get(name).or(unqualified.get(name).map(|(_, x)| x)) {
+                    errors.push(TeleoraError::Conflict { span : span.clone(), name : name.clone(), other : other.clone() });
+                } else {
+                    unqualified.insert(name.clone(), (format!("{}::{}", import.namespace, name), span.clone()));
+                }
+            }
+        }
+        for goal in goals.iter_mut() {
+            let name = loading.symbols.intern(&qualify(loading.symbols.name(goal.get_name().0)));
+            let symbols = &mut loading.symbols;
+            goal.rename(Atom(name), |called, span| {
+                let called = symbols.name(called.0).to_string();
+                let resolved = match called.rsplit_once("::") {
+                    Some((module, _)) if Some(module) == namespace => called,
+                    Some((module, name)) => match modules.get(module) {
+                        Some(exported) if exported.iter().any(|x| x == name) => called,
+                        Some(exported) => {
+                            errors.push(TeleoraError::NotExported { span : span.clone(), module : module.to_string(), name : name.to_string(), exported : exported.clone() });
+                            called
+                        },
+                        None => {
+                            let mut imported : Vec<String> = modules.keys().cloned().collect();
+                            imported.sort();
+                            errors.push(TeleoraError::UnknownModule { span : span.clone(), module : module.to_string(), imported : imported });
+                            called
+                        },
+                    },
+                    None if local.contains_key(&called) => qualify(&called),
+                    None => match unqualified.get(&called) {
+                        Some((qualified, _)) => qualified.clone(),
+                        None => called, // not a goal, the action is returned as it is
+                    },
+                };
+                return Atom(symbols.intern(&resolved));
+            });
+        }
+        // without an export list every goal of a module is visible to others
+        let mut exported : Vec<String> = match exports {
+            Some(exports) => exports.into_iter().filter(|x| local.contains_key(x)).collect(),
+            None => local.into_keys().collect(),
+        };
+        exported.sort();
+        exported.dedup();
+        if let Some(namespace) = namespace {
+            if let Some(module) = loading.modules.iter_mut().find(|x| x.namespace == namespace) {
+                module.exports = exported;
+            }
+        }
+        if let Some(file) = file {
+            errors = errors.into_iter().map(|x| x.in_file(&file)).collect();
+        }
+        loading.errors.append(&mut errors);
+        return goals;
+    }
+
+    // loads the module that `import` refers to unless it is already loaded, the goals that it exports are returned.
+    fn import(&mut self, import : &Import, directory : &Path, loading : &mut Loading, errors : &mut Vec<TeleoraError>) -> Option<Vec<String>> {
+        let candidates : Vec<PathBuf> = std::iter::once(directory).chain(self.search_path.iter().map(PathBuf::as_path))
+            .map(|x| x.join(&import.path))
+            .collect();
+        let Some(path) = candidates.iter().find(|x| x.is_file()) else {
+            errors.push(TeleoraError::ModuleNotFound { span : import.span.clone(), module : import.namespace.clone(), searched : candidates.iter().map(|x| x.display().to_string()).collect() });
+            return None;
+        };
+        let identity = canonical(path);
+        if let Some(start) = loading.stack.iter().position(|(x, _)| *x == identity) {
+            let cycle = loading.stack[start..].iter().map(|(_, x)| x.clone()).chain(std::iter::once(import.namespace.clone())).collect();
+            errors.push(TeleoraError::ImportCycle { span : import.span.clone(), cycle : cycle });
+            return None;
+        }
+        // two modules with the same name would define the same qualified goals
+        if let Some(module) = loading.modules.iter().find(|x| x.namespace == import.namespace) {
+            if module.path == identity {
+                return Some(module.exports.clone());
+            }
+            errors.push(TeleoraError::Conflict { span : import.span.clone(), name : import.namespace.clone(), other : module.span.clone() });
+            return None;
+        }
+        let file = path.display().to_string();
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                errors.push(TeleoraError::ModuleNotFound { span : import.span.clone(), module : import.namespace.clone(), searched : vec![format!("{} ({})", file, e)] });
+                return None;
+            },
+        };
+        // the module is recorded before it is loaded, its exports are known once it is
+        loading.modules.push(Module { namespace : import.namespace.clone(), path : identity.clone(), span : import.span.clone(), exports : Vec::new() });
+        match TeleoraParser::parse(AstRule::document, &source) {
+            Ok(mut document) => {
+                loading.stack.push((identity, import.namespace.clone()));
+                let mut goals = self.load_module(document.next().unwrap(), Some(&import.namespace), Some(path), loading);
+                loading.stack.pop();
+                loading.goals.append(&mut goals);
+            },
+            Err(e) => loading.errors.push(TeleoraError::syntax(e, &source).in_file(&file)),
+        }
+        self.sources.insert(Arc::from(file), source);
+        return loading.modules.iter().find(|x| x.namespace == import.namespace).map(|x| x.exports.clone());
+    }
+}
+
+impl Import {
+    fn interpret(pair : Pair<AstRule>) -> Result<Import, TeleoraError> {
+        let span = Span::from(pair.as_span());
+        let mut pairsinner = pair.into_inner();
+        let module = pairsinner.next().unwrap();
+        let (path, namespace) = match module.as_rule() {
+            // a path, the module is named by its file
+            AstRule::string => {
+                let path = PathBuf::from(Text::try_from(module)?.0);
+                let namespace = path.file_stem().map_or(String::new(), |x| x.to_string_lossy().to_string());
+                (path, namespace)
+            },
+            // a qualified name, e.g. `a::b` is the module `b` in `a/b.tela`
+            _ => {
+                let segments : Vec<&str> = module.as_str().split("::").collect();
+                let path : PathBuf = segments.iter().collect();
+                (path.with_extension("tela"), segments.last().unwrap().to_string())
+            },
+        };
+        let names = pairsinner.next().map_or(Vec::new(), names);
+        return Ok(Import { span : span, path : path, namespace : namespace, names : names });
+    }
+
+    // names the file that this import is in, see `Span::in_file`.
+    fn in_file(self, file : &str) -> Self {
+        let names = self.names.into_iter().map(|(x, span)| (x, span.in_file(file))).collect();
+        return Import { span : self.span.in_file(file), names : names, ..self };
+    }
+}
+
+// the names in a list, e.g. `{move, stop}`.
+fn names(pair : Pair<AstRule>) -> Vec<(String, Span)> {
+    let list = match pair.as_rule() {
+        AstRule::names => pair,
+        _ => pair.into_inner().next().unwrap(), // an export
+    };
+    return list.into_inner().map(|x| (x.as_str().to_string(), Span::from(x.as_span()))).collect();
+}
+
+// a path that identifies a file however it is written, if it can be found.
+fn canonical(path : &Path) -> PathBuf {
+    return path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a directory of modules that is removed once the test is done.
+    struct Directory(PathBuf);
+
+    impl Directory {
+        // writes each of `files` (a path relative to the directory and its source) to a new directory.
+        fn new(name : &str, files : &[(&str, &str)]) -> Self {
+            let root = std::env::temp_dir().join(format!("teleora-{}-{}", name, std::process::id()));
+            for (path, source) in files {
+                let path = root.join(path);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, source).unwrap();
+            }
+            return Directory(root);
+        }
+
+        // the action that the program in `main.tela` chooses for each observation, or the codes of the errors in loading it.
+        fn run(&self, loader : &mut Loader, observations : &[&str]) -> Result<Vec<String>, Vec<&'static str>> {
+            let (mut program, errors) = loader.load_file(self.0.join("main.tela"), Registry::default());
+            if !errors.is_empty() {
+                return Err(errors.iter().map(TeleoraError::code).collect());
+            }
+            return Ok(observations.iter().map(|x| {
+                let input = program.parse_observation(x).unwrap();
+                let action = program.evaluate(input).unwrap();
+                program.symbols().display(&action.0).to_string()
+            }).collect());
+        }
+    }
+
+    impl Drop for Directory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn imports_by_path_and_by_name() {
+        let directory = Directory::new("imports", &[
+            ("main.tela", "import \"lib/nav.tela\"\nimport util::{stop}\ng(X) : { X > 1 -> nav::step(X), true -> stop(X) }"),
+            ("lib/nav.tela", "step(X) : true -> move(X)"),
+            ("util.tela", "stop(_) : true -> halt"),
+        ]);
+        assert_eq!(directory.run(&mut Loader::default(), &["3", "0"]).unwrap(), ["move(3)", "halt"]);
+    }
+
+    #[test]
+    fn search_path() {
+        let directory = Directory::new("search", &[
+            ("main.tela", "import shared\ng(X) : true -> shared::twice(X)"),
+            ("lib/shared.tela", "twice(X) : true -> a(X, X)"),
+        ]);
+        assert_eq!(directory.run(&mut Loader::default(), &["1"]).unwrap_err()[0], "E0015"); // not next to `main.tela`
+        assert_eq!(directory.run(Loader::default().search(directory.0.join("lib")), &["1"]).unwrap(), ["a(1, 1)"]);
+    }
+
+    #[test]
+    fn import_cycle() {
+        let directory = Directory::new("cycle", &[
+            ("main.tela", "import a\ng(X) : true -> a::f(X)"),
+            ("a.tela", "import b\nf(X) : true -> b::f(X)"),
+            ("b.tela", "import a\nf(X) : true -> x"),
+        ]);
+        assert_eq!(directory.run(&mut Loader::default(), &["1"]), Err(vec!["E0016"]));
+    }
+
+    #[test]
+    fn exports_and_conflicts() {
+        let directory = Directory::new("exports", &[
+            ("main.tela", "import nav\ng(X) : true -> nav::helper(X)"),
+            ("nav.tela", "export {step}\nstep(X) : true -> helper(X)\nhelper(X) : true -> move(X)"),
+        ]);
+        assert_eq!(directory.run(&mut Loader::default(), &["1"]), Err(vec!["E0018"]));
+        // the goals of a module may call those that it does not export
+        let directory = Directory::new("exports-used", &[
+            ("main.tela", "import nav\ng(X) : true -> nav::step(X)"),
+            ("nav.tela", "export {step}\nstep(X) : true -> helper(X)\nhelper(X) : true -> move(X)"),
+        ]);
+        assert_eq!(directory.run(&mut Loader::default(), &["1"]).unwrap(), ["move(1)"]);
+        let directory = Directory::new("conflict", &[
+            ("main.tela", "import util::{stop}\ng(X) : true -> stop(X)\nstop(_) : true -> local"),
+            ("util.tela", "stop(_) : true -> halt"),
+        ]);
+        assert_eq!(directory.run(&mut Loader::default(), &["1"]), Err(vec!["E0017"]));
+    }
+}
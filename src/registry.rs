@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::error::TeleoraError;
+use crate::statement::{Statement, EvaluationError};
+use crate::statement::function::Function;
+use crate::statement::builtin::FUNCTIONS;
+
+/// The functions and infix operators that rules may use, in addition to the operators of the grammar. An application that
+/// embeds the interpreter registers its own (e.g. `angle_between`) before loading a program, names in the program are
+/// looked up when it is loaded.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    functions : HashMap<Arc<str>, Function>, // called as `name(X, Y)`
+    operators : HashMap<Arc<str>, Function>, // written between their arguments as `X name Y`
+}
+
+impl Default for Registry {
+    /// a registry containing the builtin functions (e.g. `len`, `concat`).
+    fn default() -> Self {
+        let mut registry = Registry::empty();
+        for (name, arity, builtin) in FUNCTIONS {
+            registry.functions.insert(name.into(), Function::new(name, arity, None, Arc::new(builtin)));
+        }
+        return registry;
+    }
+}
+
+impl Registry {
+    /// a registry without any functions, not even the builtin ones.
+    pub fn empty() -> Self {
+        return Registry { functions : HashMap::new(), operators : HashMap::new() };
+    }
+
+    /// registers a function that takes `arity` arguments, it replaces any function with the same name. The name must be
+    /// written like an atom (e.g. `angle_between`).
+    pub fn function<S, F>(&mut self, name : S, arity : usize, implementation : F) -> Result<&mut Self, TeleoraError>
+    where S : Into<String>, F : Fn(&[Statement]) -> Result<Statement, EvaluationError> + Send + Sync + 'static {
+        let name : Arc<str> = check_name(name.into())?.into();
+        self.functions.insert(name.clone(), Function::new(name, arity, None, Arc::new(implementation)));
+        return Ok(self);
+    }
+
+    /// registers an infix operator, `X name Y` is applied to the values of `X` and `Y`. It replaces any operator with the
+    /// same name, the name must be written like an atom (e.g. `dot`). Operators bind less tightly than arithmetic and more
+    /// tightly than comparisons, e.g. `A + 1 dot B > 0` is `((A + 1) dot B) > 0`. Between themselves, an operator with a
+    /// higher `precedence` binds more tightly and operators with the same precedence are left associative, e.g. if `dot`
+    /// has a higher precedence than `cross` then `A cross B dot C` is `A cross (B dot C)`.
+    pub fn operator<S, F>(&mut self, name : S, precedence : u8, implementation : F) -> Result<&mut Self, TeleoraError>
+    where S : Into<String>, F : Fn(&Statement, &Statement) -> Result<Statement, EvaluationError> + Send + Sync + 'static {
+        let name : Arc<str> = check_name(name.into())?.into();
+        let implementation = move |arguments : &[Statement]| implementation(&arguments[0], &arguments[1]);
+        self.operators.insert(name.clone(), Function::new(name, 2, Some(precedence), Arc::new(implementation)));
+        return Ok(self);
+    }
+
+    pub fn get_function(&self, name : &str) -> Option<&Function> {
+        return self.functions.get(name);
+    }
+
+    pub fn get_operator(&self, name : &str) -> Option<&Function> {
+        return self.operators.get(name);
+    }
+
+    /// the names of the registered functions, in alphabetical order.
+    pub fn function_names(&self) -> Vec<String> {
+        let mut names : Vec<String> = self.functions.keys().map(|x| x.to_string()).collect();
+        names.sort();
+        return names;
+    }
+
+    /// the names of the registered operators, in alphabetical order.
+    pub fn operator_names(&self) -> Vec<String> {
+        let mut names : Vec<String> = self.operators.keys().map(|x| x.to_string()).collect();
+        names.sort();
+        return names;
+    }
+}
+
+// words of the grammar that cannot be used as the name of a function or operator.
+const KEYWORDS : [&str; 6] = ["true", "false", "in", "union", "intersect", "minus"];
+
+// names are parsed as atoms, so they must begin with a lowercase letter and cannot be a keyword.
+fn check_name(name : String) -> Result<String, TeleoraError> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|x| x.is_ascii_lowercase()) && chars.all(|x| x.is_ascii_alphanumeric() || x == '_');
+    if !valid || KEYWORDS.contains(&name.as_str()) {
+        return Err(TeleoraError::InvalidName { name : name, reserved : KEYWORDS.to_vec() });
+    }
+    return Ok(name);
+}
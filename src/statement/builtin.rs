@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use regex::Regex;
+use crate::statement::{Statement, Text, Boolean, IntegerValue, AsStatement, EvaluationError};
+
+/// The signature of a builtin function, see `Implementation`.
+pub type Builtin = fn(&[Statement]) -> Result<Statement, EvaluationError>;
+
+/// The functions provided by the interpreter (name, arity and implementation), every `Registry` starts with these.
+pub const FUNCTIONS : [(&str, usize, Builtin); 5] = [
+    ("concat", 2, concat),
+    ("starts_with", 2, starts_with),
+    ("contains", 2, contains),
+    ("len", 1, len),
+    ("matches", 2, matches),
+];
+
+/// `concat(X, Y)` joins two strings.
+pub fn concat(arguments : &[Statement]) -> Result<Statement, EvaluationError> {
+    return match arguments {
+        [Statement::Text(x), Statement::Text(y)] => Ok(Text(format!("{}{}", x.0, y.0)).as_statement()),
+        _ => Err(mismatch("concat", arguments)),
+    };
+}
+
+/// `starts_with(X, Y)` whether the string `X` begins with the string `Y`.
+pub fn starts_with(arguments : &[Statement]) -> Result<Statement, EvaluationError> {
+    return match arguments {
+        [Statement::Text(x), Statement::Text(y)] => Ok(Boolean(x.0.starts_with(y.0.as_str())).as_statement()),
+        _ => Err(mismatch("starts_with", arguments)),
+    };
+}
+
+/// `contains(X, Y)` whether the string `Y` occurs in the string `X`.
+pub fn contains(arguments : &[Statement]) -> Result<Statement, EvaluationError> {
+    return match arguments {
+        [Statement::Text(x), Statement::Text(y)] => Ok(Boolean(x.0.contains(y.0.as_str())).as_statement()),
+        _ => Err(mismatch("contains", arguments)),
+    };
+}
+
+/// `len(X)` the number of characters in a string or items in a collection.
+pub fn len(arguments : &[Statement]) -> Result<Statement, EvaluationError> {
+    let n = match arguments {
+        [Statement::Text(x)]                           => x.0.chars().count(),
+        [Statement::List(x)] if x.spreads() == 0       => x.len(),
+        [Statement::UList(x)] if x.spreads() == 0      => x.len(),
+        [Statement::Object(x)] if x.spreads() == 0     => x.len(),
+        [Statement::Sequence(x)]                       => x.len(),
+        _ => return Err(mismatch("len", arguments)),
+    };
+    return Ok((n as IntegerValue).as_statement());
+}
+
+/// `matches(X, P)` whether the regular expression `P` matches some part of the string `X`.
+pub fn matches(arguments : &[Statement]) -> Result<Statement, EvaluationError> {
+    return match arguments {
+        [Statement::Text(x), Statement::Text(y)] => match pattern(&y.0) {
+            Ok(pattern) => Ok(Boolean(pattern.is_match(&x.0)).as_statement()),
+            Err(reason) => Err(EvaluationError::InvalidArgument { function : "matches", argument : arguments[1].clone(), reason : reason }),
+        },
+        _ => Err(mismatch("matches", arguments)),
+    };
+}
+
+lazy_static::lazy_static! {
+    // the patterns that `matches` has compiled (or failed to), a rule evaluated for every observation compiles its pattern once
+    static ref PATTERNS : Mutex<HashMap<String, Result<Regex, String>>> = Mutex::new(HashMap::new());
+}
+
+const PATTERNS_HELD : usize = 256; // the patterns are forgotten once there are this many, e.g. if they are built at run time
+
+// the regular expression written in `source`, compiled once however often it is used.
+fn pattern(source : &str) -> Result<Regex, String> {
+    let mut patterns = PATTERNS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(pattern) = patterns.get(source) {
+        return pattern.clone();
+    }
+    if patterns.len() >= PATTERNS_HELD {
+        patterns.clear();
+    }
+    let pattern = Regex::new(source).map_err(|e| e.to_string());
+    patterns.insert(source.to_string(), pattern.clone());
+    return pattern;
+}
+
+fn mismatch(function : &'static str, arguments : &[Statement]) -> EvaluationError {
+    return EvaluationError::TypeMismatch { operator : function, operands : arguments.to_vec() };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::statement::Numbers;
+    use crate::interpret::tests::expression;
+
+    // the value of the expression written in `source`, as it is displayed.
+    fn evaluate(source : &str) -> Result<String, EvaluationError> {
+        let (statement, symbols) = expression(source);
+        return statement.evaluate(&Numbers::default()).map(|x| symbols.display(&x).to_string());
+    }
+
+    #[test]
+    fn string_functions() {
+        assert_eq!(evaluate(r#"concat("Traffic", " Light")"#).unwrap(), r#""Traffic Light""#);
+        assert_eq!(evaluate(r#"starts_with("Traffic Light", "Traffic")"#).unwrap(), "true");
+        assert_eq!(evaluate(r#"starts_with("Traffic Light", "Light")"#).unwrap(), "false");
+        assert_eq!(evaluate(r#"contains("Traffic Light", "c L")"#).unwrap(), "true");
+        assert_eq!(evaluate(r#"contains("Traffic Light", "light")"#).unwrap(), "false");
+        // characters are counted rather than bytes
+        assert_eq!(evaluate(r#"len("héllo")"#).unwrap(), "5");
+        assert_eq!(evaluate("len([1, 2, 3])").unwrap(), "3");
+        assert_eq!(evaluate("len({a : 1})").unwrap(), "1");
+        for source in [r#"concat("a", 1)"#, "len(a)", r#"starts_with(1, "a")"#, r#"matches("a", b)"#] {
+            assert!(matches!(evaluate(source), Err(EvaluationError::TypeMismatch { .. })), "{}", source);
+        }
+    }
+
+    #[test]
+    fn regular_expressions() {
+        assert_eq!(evaluate(r#"matches("Traffic Light", "^T.*t$")"#).unwrap(), "true");
+        assert_eq!(evaluate(r#"matches("Stop Sign", "Light|Lamp")"#).unwrap(), "false");
+        let error = evaluate(r#"matches("a", "(a")"#).unwrap_err();
+        assert!(matches!(&error, EvaluationError::InvalidArgument { function : "matches", .. }), "{:?}", error);
+        // a pattern is compiled once, and so is one that does not compile
+        assert!(PATTERNS.lock().unwrap().contains_key("^T.*t$"));
+        assert!(PATTERNS.lock().unwrap().get("(a").is_some_and(|x| x.is_err()));
+    }
+}
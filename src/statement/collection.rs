@@ -1,13 +1,14 @@
 use std::ops::{Deref, Index};
+use std::rc::Rc;
 use crate::statement::Statement;
 use std::fmt::Debug;
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Collection<T> { 
     pub items: T,
 }
 
-pub type Sequence = Collection<Vec<Statement>>;
+pub type Sequence = Collection<Rc<[Statement]>>; // items are shared, so cloning a sequence is cheap
 pub type SequenceRef<'a> = Collection<&'a [Statement]>;
 
 impl<S, T: Deref<Target=[S]>> Collection<T> {
@@ -23,16 +24,20 @@ impl<S, T: Deref<Target=[S]>> Collection<T> {
     pub fn len(&self) -> usize {
         return self.items.len();
     }
+
+    pub fn is_empty(&self) -> bool {
+        return self.items.is_empty();
+    }
 }
 
-impl Collection<Vec<Statement>> {
+impl Collection<Rc<[Statement]>> {
     //methods only for the owned version go here
 
     pub fn new(items : Vec<Statement>) -> Self {
-        return Self { items : items};
+        return Self { items : Rc::from(items)};
     }
 
-    pub fn as_ref(&self) -> SequenceRef  {
+    pub fn as_ref(&self) -> SequenceRef<'_>  {
         return Collection{ items: &self.items}
     }    
 }
@@ -51,7 +56,7 @@ impl<S, T: Deref<Target=[S]>>  Index<usize> for Collection<T> {
     }
 }
 
-impl<'a, S : Debug, T : Deref<Target=[S]>> std::fmt::Debug for Collection<T> {
+impl<S : Debug, T : Deref<Target=[S]>> std::fmt::Debug for Collection<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut iter = self.items.iter();
         if let Some(item) = iter.next() {
@@ -65,30 +70,23 @@ impl<'a, S : Debug, T : Deref<Target=[S]>> std::fmt::Debug for Collection<T> {
 }
 
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
 pub struct List {
     pub items : Sequence,
     pub ispiped : bool,
 }
 
-#[derive(Clone, PartialEq)]
+// ulists and objects are equal (and hash equally) whatever the order of their items, see `order.rs`
+#[derive(Clone, Default)]
 pub struct UList {
     pub items : Sequence,
     pub ispiped : bool,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct Object(pub (Sequence, bool));
 
 
 impl Default for Sequence {
-    fn default() -> Self { Self { items : Vec::new() }}
+    fn default() -> Self { Self { items : Rc::from(Vec::new()) }}
 }
-
-impl Default for UList {
-    fn default() -> Self { Self { ..Default::default() }}
-}
-
-impl Default for List {
-    fn default() -> Self { Self { ..Default::default() }}
-}
\ No newline at end of file
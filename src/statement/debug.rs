@@ -1,12 +1,5 @@
 
-use crate::statement::{Statement, List, UList, Sequence};
-
-// reason for failure
-enum EvaluateDebug {
-    Length(Statement, Statement),
-    Primitive(Statement, Statement),
-    Type(Statement, Statement),
-}
+use crate::statement::{List, UList};
 
 
 impl std::fmt::Debug for List {
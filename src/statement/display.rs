@@ -0,0 +1,200 @@
+use std::fmt;
+use crate::statement::{Statement, Symbols, BinaryOperator, UnaryOperator, Object, Call};
+
+/// Displays a statement as it would be written in a program, the names of atoms and variables are looked up in `symbols`.
+pub struct StatementDisplay<'a> {
+    statement : &'a Statement,
+    symbols : &'a Symbols,
+}
+
+impl Symbols {
+    pub fn display<'a>(&'a self, statement : &'a Statement) -> StatementDisplay<'a> {
+        return StatementDisplay { statement : statement, symbols : self };
+    }
+}
+
+impl StatementDisplay<'_> {
+
+    fn child<'b>(&'b self, statement : &'b Statement) -> StatementDisplay<'b> {
+        return StatementDisplay { statement : statement, symbols : self.symbols };
+    }
+
+    fn write_items(&self, f : &mut fmt::Formatter<'_>, items : &[Statement], ispiped : bool) -> fmt::Result {
+        let (items, tail) = match (ispiped, items.split_last()) {
+            (true, Some((tail, items))) => (items, Some(tail)),
+            _ => (items, None),
+        };
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", self.child(item))?;
+        }
+        if let Some(tail) = tail {
+            write!(f, " | {}", self.child(tail))?;
+        }
+        return Ok(());
+    }
+
+    // entries of an object are written as `key : value`, other items (spreads or a tail) are written as they are.
+    fn write_entries(&self, f : &mut fmt::Formatter<'_>, items : &[Statement], ispiped : bool) -> fmt::Result {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", if ispiped && i == items.len() - 1 { " | " } else { ", " })?;
+            }
+            match item {
+                Statement::Sequence(x) if x.len() == 2 => write!(f, "{} : {}", self.child(&x[0]), self.child(&x[1]))?,
+                x => write!(f, "{}", self.child(x))?,
+            }
+        }
+        return Ok(());
+    }
+
+    // operands are parenthesised if their operator binds less tightly than `precedence` (operators are left associative, 
+    // so the right operand is also parenthesised if its operator binds equally tightly). Operators registered by the host
+    // share a precedence of the grammar and are ordered between themselves by their own.
+    fn write_operand(&self, f : &mut fmt::Formatter<'_>, operand : &Statement, precedence : (usize, u8), right : bool) -> fmt::Result {
+        let inner = match operand {
+            Statement::BinaryOperator(x) => (x.precedence(), 0),
+            Statement::UnaryOperator(_) => (UnaryOperator::PRECEDENCE, 0),
+            Statement::Call(x) => match x.function.infix {
+                Some(p) => (Call::PRECEDENCE, p),
+                None => (usize::MAX, 0),
+            },
+            _ => (usize::MAX, 0),
+        };
+        if inner < precedence || (right && inner == precedence) {
+            return write!(f, "({})", self.child(operand));
+        }
+        return write!(f, "{}", self.child(operand));
+    }
+}
+
+impl fmt::Display for StatementDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self.statement {
+            Statement::Float(x) => write!(f, "{:?}", x.0),
+            Statement::Integer(x) => write!(f, "{}", x.0),
+            Statement::Rational(x) => write!(f, "{}/{}", x.numerator, x.denominator),
+            Statement::Boolean(x) => write!(f, "{}", x.0),
+            Statement::Atom(x) => write!(f, "{}", self.symbols.name(x.0)),
+            Statement::Text(x) => write!(f, "{:?}", x.0), // escaped as in the grammar
+            Statement::Variable(x) => write!(f, "{}", self.symbols.name(x.name)),
+            Statement::Compound(x) => {
+                write!(f, "{}(", self.symbols.name(x.name.0))?;
+                self.write_items(f, &x.arguments.items, false)?;
+                write!(f, ")")
+            },
+            Statement::Call(x) if x.function.infix.is_some() => {
+                let precedence = (Call::PRECEDENCE, x.function.infix.unwrap_or_default());
+                self.write_operand(f, &x.arguments[0], precedence, false)?;
+                write!(f, " {} ", x.function.name)?;
+                self.write_operand(f, &x.arguments[1], precedence, true)
+            },
+            Statement::Call(x) => {
+                write!(f, "{}(", x.function.name)?;
+                self.write_items(f, &x.arguments.items, false)?;
+                write!(f, ")")
+            },
+            Statement::BinaryOperator(BinaryOperator::Index(x, y)) => {
+                self.write_operand(f, x, (usize::MAX, 0), false)?;
+                match y.as_ref() {
+                    Statement::Atom(_) => write!(f, ".{}", self.child(y)),
+                    _ => write!(f, "[{}]", self.child(y)),
+                }
+            },
+            Statement::BinaryOperator(x) => {
+                let (lhs, rhs) = x.operands();
+                self.write_operand(f, lhs, (x.precedence(), 0), false)?;
+                write!(f, " {} ", x.symbol())?;
+                self.write_operand(f, rhs, (x.precedence(), 0), true)
+            },
+            Statement::UnaryOperator(x) => {
+                write!(f, "{}", x.symbol())?;
+                self.write_operand(f, x.operand(), (UnaryOperator::PRECEDENCE, 0), false)
+            },
+            Statement::Sequence(x) => {
+                write!(f, "(")?;
+                self.write_items(f, &x.items, false)?;
+                write!(f, ")")
+            },
+            Statement::List(x) => {
+                write!(f, "[")?;
+                self.write_items(f, &x.items.items, x.ispiped)?;
+                write!(f, "]")
+            },
+            Statement::UList(x) => {
+                write!(f, "{{")?;
+                self.write_items(f, &x.items.items, x.ispiped)?;
+                write!(f, "}}")
+            },
+            Statement::Object(Object((x, ispiped))) => {
+                write!(f, "{{")?;
+                self.write_entries(f, &x.items, *ispiped)?;
+                write!(f, "}}")
+            },
+            Statement::Spread(x) => write!(f, "*{}", self.child(x)),
+            Statement::Empty => Ok(()),
+        };
+    }
+}
+
+impl BinaryOperator {
+    /// the symbol used to write this operator.
+    pub fn symbol(&self) -> &'static str {
+        return match self {
+            BinaryOperator::Add(_, _)               => "+",
+            BinaryOperator::Subtract(_, _)          => "-",
+            BinaryOperator::Multiply(_, _)          => "*",
+            BinaryOperator::Divide(_, _)            => "/",
+            BinaryOperator::Modulo(_, _)            => "%",
+            BinaryOperator::And(_, _)               => "&&",
+            BinaryOperator::Or(_, _)                => "||",
+            BinaryOperator::GreaterThan(_, _)       => ">",
+            BinaryOperator::GreaterThanEqual(_, _)  => ">=",
+            BinaryOperator::LessThan(_, _)          => "<",
+            BinaryOperator::LessThanEqual(_, _)     => "<=",
+            BinaryOperator::Equal(_, _)             => "=",
+            BinaryOperator::NotEqual(_, _)          => "!=",
+            BinaryOperator::Index(_, _)             => "[]",
+            BinaryOperator::Concat(_, _)            => "++",
+            BinaryOperator::In(_, _)                => "in",
+            BinaryOperator::Union(_, _)             => "union",
+            BinaryOperator::Intersect(_, _)         => "intersect",
+            BinaryOperator::Difference(_, _)        => "minus",
+        };
+    }
+
+    /// how tightly this operator binds its operands when written, as in the grammar (higher binds more tightly).
+    pub fn precedence(&self) -> usize {
+        return match self {
+            BinaryOperator::Equal(_, _) | BinaryOperator::GreaterThan(_, _) | BinaryOperator::GreaterThanEqual(_, _) 
+                | BinaryOperator::LessThan(_, _) | BinaryOperator::LessThanEqual(_, _) | BinaryOperator::In(_, _) 
+                | BinaryOperator::NotEqual(_, _) => 3,
+            BinaryOperator::Or(_, _) => 1,
+            BinaryOperator::And(_, _) => 2,
+            BinaryOperator::Add(_, _) | BinaryOperator::Subtract(_, _) | BinaryOperator::Concat(_, _) 
+                | BinaryOperator::Union(_, _) | BinaryOperator::Difference(_, _) => 5,
+            BinaryOperator::Multiply(_, _) | BinaryOperator::Divide(_, _) | BinaryOperator::Modulo(_, _) | BinaryOperator::Intersect(_, _) => 6,
+            BinaryOperator::Index(_, _) => 8,
+        };
+    }
+}
+
+impl UnaryOperator {
+    /// how tightly unary operators bind their operand when written (see `BinaryOperator::precedence`).
+    pub const PRECEDENCE : usize = 7;
+
+    /// the symbol used to write this operator.
+    pub fn symbol(&self) -> &'static str {
+        return match self {
+            UnaryOperator::Minus(_) => "-",
+            UnaryOperator::Not(_)   => "!",
+        };
+    }
+}
+
+impl Call {
+    /// how tightly infix operators registered by the host bind their operands when written (see `BinaryOperator::precedence`).
+    pub const PRECEDENCE : usize = 4;
+}
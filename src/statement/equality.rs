@@ -0,0 +1,76 @@
+use std::cmp::Ordering;
+use crate::statement::{Statement, Float, Integer, Rational, FloatValue};
+use crate::statement::operators::Compare;
+
+/// Policy used to decide whether two numbers are equal, both when unifying and when evaluating `=`, `!=`, `in` and the
+/// ulist operators.
+///
+/// Two floats are equal if they differ by at most `absolute`, or by at most `relative` times the larger of
+/// their magnitudes. When `mixed` is set an `Integer` is compared with a `Float` by value (i.e. `1 = 1.0`),
+/// otherwise integers and floats never match. Integers are always compared exactly with each other.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NumericEquality {
+    pub absolute : f64,
+    pub relative : f64,
+    pub mixed : bool,
+}
+
+impl Default for NumericEquality {
+    fn default() -> Self { return NumericEquality::exact(); }
+}
+
+impl NumericEquality {
+
+    /// numbers are equal only if they have the same value, integers and floats may be equal.
+    pub fn exact() -> Self {
+        return NumericEquality { absolute : 0.0, relative : 0.0, mixed : true };
+    }
+
+    /// numbers are equal only if they have the same value and type.
+    pub fn strict() -> Self {
+        return NumericEquality { absolute : 0.0, relative : 0.0, mixed : false };
+    }
+
+    /// floats are equal if they are within `absolute` or `relative` tolerance of each other.
+    pub fn tolerant(absolute : f64, relative : f64) -> Self {
+        return NumericEquality { absolute : absolute, relative : relative, mixed : true };
+    }
+
+    pub fn float(&self, x : f64, y : f64) -> bool {
+        if x == y {
+            return true;
+        }
+        if !x.is_finite() || !y.is_finite() { // NaN is never equal, infinities are only equal to themselves.
+            return false;
+        }
+        let difference = (x - y).abs();
+        return difference <= self.absolute || difference <= self.relative * x.abs().max(y.abs());
+    }
+
+    // an integer and a float are equal if they have the same value (the integer is not rounded) or are within tolerance.
+    fn integer_float(&self, x : &Integer, y : &Float) -> bool {
+        if !self.mixed {
+            return false;
+        }
+        let tolerant = self.absolute > 0.0 || self.relative > 0.0;
+        return x.compare(y) == Some(Ordering::Equal) || (tolerant && self.float(x.0 as FloatValue, y.0));
+    }
+
+    /// compares two numeric statements, returns `None` if either of them is not a number.
+    pub fn numeric(&self, x : &Statement, y : &Statement) -> Option<bool> {
+        let result = match (x, y) {
+            (Statement::Integer(Integer(xx)), Statement::Integer(Integer(yy))) => xx == yy,
+            (Statement::Float(Float(xx)),     Statement::Float(Float(yy)))     => self.float(*xx, *yy),
+            (Statement::Integer(xx),          Statement::Float(yy))            => self.integer_float(xx, yy),
+            (Statement::Float(xx),            Statement::Integer(yy))          => self.integer_float(yy, xx),
+            // rationals are exact, like integers
+            (Statement::Rational(xx),         Statement::Rational(yy))         => xx == yy,
+            (Statement::Rational(xx),         Statement::Integer(yy))          => *xx == Rational::from(*yy),
+            (Statement::Integer(xx),          Statement::Rational(yy))         => Rational::from(*xx) == *yy,
+            (Statement::Rational(xx),         Statement::Float(Float(yy)))     => self.mixed && self.float(xx.to_float(), *yy),
+            (Statement::Float(Float(xx)),     Statement::Rational(yy))         => self.mixed && self.float(*xx, yy.to_float()),
+            _ => return None,
+        };
+        return Some(result);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::statement::{Statement, Variable, Symbols};
+
+/// An error that occurs while evaluating an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvaluationError {
+    Unbound(Variable),                                                  // a variable that has no value
+    DivisionByZero(Statement),                                          // the expression that divides by zero
+    Overflow(Statement),                                                // the expression whose result cannot be represented
+    NoElement(Statement),                                               // the index expression whose collection has no such element
+    NotBoolean(Statement),                                              // the value of a condition that is not a boolean
+    NotAction(Statement),                                               // the value of an action that is not an atom or a compound
+    TypeMismatch { operator : &'static str, operands : Vec<Statement> }, // an operator was applied to operands it does not support
+    InvalidArgument { function : &'static str, argument : Statement, reason : String }, // e.g. a regular expression that does not compile
+}
+
+/// The kinds of evaluation error, an unbound variable may be bound later (e.g. by a condition that follows) while the others
+/// are a problem with the values themselves.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    Unbound,
+    Type,           // an operator or function was applied to values that it does not support
+    Arithmetic,     // e.g. division by zero or overflow
+}
+
+impl EvaluationError {
+    pub fn kind(&self) -> ErrorKind {
+        return match self {
+            EvaluationError::Unbound(_) => ErrorKind::Unbound,
+            EvaluationError::DivisionByZero(_) | EvaluationError::Overflow(_) => ErrorKind::Arithmetic,
+            EvaluationError::NotBoolean(_) | EvaluationError::NotAction(_) | EvaluationError::NoElement(_) | EvaluationError::TypeMismatch { .. } | EvaluationError::InvalidArgument { .. } => ErrorKind::Type,
+        };
+    }
+
+    /// describes this error, the names of atoms and variables are looked up in `symbols`.
+    pub fn message(&self, symbols : &Symbols) -> String {
+        return match self {
+            EvaluationError::Unbound(x) => format!("Variable `{}` is unbound, it has no value to evaluate.", symbols.name(x.name)),
+            EvaluationError::DivisionByZero(x) => format!("Division by zero in `{}`.", symbols.display(x)),
+            EvaluationError::NotBoolean(x) => format!("Type mismatch, a condition must be `true` or `false` but found `{}`.", symbols.display(x)),
+            EvaluationError::NotAction(x) => format!("Type mismatch, an action must be an atom or a compound but found `{}`.", symbols.display(x)),
+            EvaluationError::NoElement(x) => format!("There is no element `{}`, the collection has no such position or key.", symbols.display(x)),
+            EvaluationError::Overflow(x) => format!("Arithmetic overflow in `{}`, the result is too large to represent.", symbols.display(x)),
+            EvaluationError::InvalidArgument { function, argument, reason } => format!("Invalid argument `{}` to `{}`, {}", symbols.display(argument), function, reason),
+            EvaluationError::TypeMismatch { operator, operands } => {
+                let operands : Vec<String> = operands.iter().map(|x| format!("`{}`", symbols.display(x))).collect();
+                format!("Type mismatch, `{}` cannot be applied to {}.", operator, operands.join(" and "))
+            },
+        };
+    }
+
+    /// whether evaluating `expression` (as it is written, with variables) could have raised this error, e.g. `X / Y` could
+    /// have raised a division by zero in `3 / 0`. Used to find where an error was raised, since statements carry no spans.
+    pub fn raised_by(&self, expression : &Statement) -> bool {
+        // the operands of the error must be values of the operands of the expression
+        let operands = |written : &[&Statement], values : &[Statement]| values.iter().all(|x| written.iter().any(|y| generalises(y, x)));
+        return match (self, expression) {
+            (EvaluationError::DivisionByZero(x) | EvaluationError::Overflow(x) | EvaluationError::NoElement(x), y) => generalises(y, x),
+            (EvaluationError::TypeMismatch { operator, operands : values }, Statement::BinaryOperator(x)) => x.symbol() == *operator && { let (lhs, rhs) = x.operands(); operands(&[lhs, rhs], values) },
+            (EvaluationError::TypeMismatch { operator, operands : values }, Statement::UnaryOperator(x)) => x.symbol() == *operator && operands(&[x.operand()], values),
+            (EvaluationError::TypeMismatch { operator, operands : values }, Statement::Call(x)) => *x.function.name == **operator && operands(&x.arguments.iter().collect::<Vec<_>>(), values),
+            (EvaluationError::InvalidArgument { function, argument, .. }, Statement::Call(x)) => *x.function.name == **function && x.arguments.iter().any(|y| generalises(y, argument)),
+            _ => false,
+        };
+    }
+}
+
+// whether `value` could be the value of `written`, i.e. they are the same except where `written` has a variable or an
+// expression (whose value it could be).
+fn generalises(written : &Statement, value : &Statement) -> bool {
+    if matches!(written, Statement::Variable(_) | Statement::BinaryOperator(_) | Statement::UnaryOperator(_) | Statement::Call(_)) {
+        return true;
+    }
+    let (xx, yy) = (written.children(), value.children());
+    if xx.is_empty() {
+        return written == value;
+    }
+    return std::mem::discriminant(written) == std::mem::discriminant(value) && xx.len() == yy.len() && xx.iter().zip(yy.iter()).all(|(x, y)| generalises(x, y));
+}
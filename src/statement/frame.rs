@@ -1,294 +1,695 @@
-use std::{collections::HashMap};
-use crate::statement::{Statement, Variable, UList, List, AsStatement};
-use crate::statement::collection::Collection;
+use std::rc::Rc;
+use crate::statement::{Statement, Variable, UList, List, Object, Sequence, AsStatement, Numbers, BinaryOperator, Integer, IntegerValue, Boolean, EvaluationError};
+use crate::error::{Failure, Span};
+use crate::statement::invert::{invert, non_invertible};
+use crate::statement::operator_binary::members;
+
+/// Called each time a match succeeds with the frame holding its bindings. Returning `true` accepts the match and stops the
+/// search (the bindings are kept), returning `false` asks for the next match (the bindings are undone before trying again).
+pub type Continuation<'k> = dyn FnMut(&mut Frame) -> bool + 'k;
+
+/// Called with a statement in which each index expression has been replaced by the element that it refers to, see `Frame::resolve`.
+pub type Resolved<'k> = dyn FnMut(&mut Frame, &Statement) -> bool + 'k;
 
 impl Frame {
 
-    //pub fn evaluate_sequence(seq1: & Collection<& [Statement]>, seq2: & Collection<&[Statement]>, frame: & Frame)  -> Box<dyn Iterator<Item = Frame> > {
-    pub fn evaluate_sequence(seq1: &Collection<& [Statement]>, seq2: & Collection<& [Statement]>, frame: &Frame)  -> Box<dyn Iterator<Item = Frame>> {
-        // evaluate the first elements of seq1 and seq2. This should produce an iterator over Frame objects.
-        // the Frames should contain any variables which were grounded during the evaluation.
+    pub fn evaluate_sequence(&mut self, seq1 : &[Statement], seq2 : &[Statement], k : &mut Continuation) -> bool {
+        // elements are matched in order, each element is matched using the bindings that were made by those before it.
         if seq1.len() != seq2.len() {
-            println!("Failed to match, lengths: {:?} != {:?}", seq1.len(), seq2.len()); 
-            return empty(); // this failed. TODO debug...
+            return false;
         }
-        return FrameGenerator::new(seq1.items, seq2.items, singleton(frame.clone()));
-    }   
-
-    pub fn evaluate_ulist(l1 : &UList, l2 : &UList, frame : &Frame) -> Box<dyn Iterator<Item = Frame>> {
-        // ulists are hard to deal with efficiently as permuations can be expensive. There are common special cases to consider:
-        // e.g. {A|_} = {1,2} which will produce 2 frames A=1, A=2.
-
-        if !l1.ispiped && !l2.ispiped { // simplest case
-            // TODO for now check that one side is grounded
-            let l1items = l2.items.as_ref();
-            let mut l2items = l1.items.as_ref().to_vec().clone(); // TODO any better way that doesnt need a copy?
-            
-            println!("{:?} --- {:?}", l1items.items, l2items.items);
-
-            //let permiter = PermutationIterator::new(l1items.items, &mut l2items.items);
-            
+        return match (seq1.split_first(), seq2.split_first()) {
+            (Some((x1, s1)), Some((x2, s2))) => self.evaluate_statement(x1, x2, &mut |frame| frame.evaluate_sequence(s1, s2, k)),
+            _ => k(self), // nothing left to match
+        };
+    }
 
-            //for p in permiter {
-            //   println!("??{:?}", p);
-            //}
+    pub fn evaluate_ulist(&mut self, l1 : &UList, l2 : &UList, k : &mut Continuation) -> bool {
+        // ulists are hard to deal with efficiently as permuations can be expensive, each item is matched with each of the
+        // remaining items in turn. e.g. {A|_} = {1,2} will produce 2 matches A=1, A=2.
+        // TODO {A|B} = {1|{2}} recall that {A|{B}} = {A,B} in this case it may be better to unpack {1|{2}}
+        if l1.spreads() == 0 && l2.spreads() > 0 {
+            return self.evaluate_ulist(l2, l1, k);
+        }
+        let (items1, rest1) = unordered(&l1.items, l1.ispiped);
+        let (items2, rest2) = unordered(&l2.items, l2.ispiped);
+        if rest2.is_some() {
+            return false; // both capture their remaining items
+        }
+        let mut used = vec![false; items2.len()];
+        return self.evaluate_unordered(&items1, rest1.as_ref(), &items2, &mut used, &|x| UList::new(Sequence::new(x), false).as_statement(), k);
+    }
 
+    pub fn evaluate_object(&mut self, o1 : &Object, o2 : &Object, k : &mut Continuation) -> bool {
+        // entries are (key, value) sequences, the entries of an object are unordered.
+        if o1.spreads() == 0 && o2.spreads() > 0 {
+            return self.evaluate_object(o2, o1, k);
+        }
+        let (items1, rest1) = unordered(&o1.0.0, o1.0.1);
+        let (items2, rest2) = unordered(&o2.0.0, o2.0.1);
+        if rest2.is_some() {
+            return false; // both capture their remaining items
+        }
+        let mut used = vec![false; items2.len()];
+        return self.evaluate_unordered(&items1, rest1.as_ref(), &items2, &mut used, &|x| Object::new(Sequence::new(x), false).as_statement(), k);
+    }
 
-            
-            // {A,A} = {1,2} -> fails, but how to do this efficiently?
+    /// each of `items1` is matched with a different item of `items2` (one that is not yet `used`), the items of `items2` that
+    /// remain are collected and matched with `rest1`.
+    fn evaluate_unordered(&mut self, items1 : &[Statement], rest1 : Option<&Statement>, items2 : &[Statement], used : &mut [bool], collect : &dyn Fn(Vec<Statement>) -> Statement, k : &mut Continuation) -> bool {
+        let remaining = used.iter().filter(|x| !**x).count();
+        if items1.len() > remaining || (rest1.is_none() && items1.len() != remaining) {
+            return false;
+        }
+        let Some((x1, s1)) = items1.split_first() else {
+            return match rest1 {
+                Some(rest) => {
+                    let others = items2.iter().zip(used.iter()).filter(|(_, used)| !**used).map(|(x, _)| x.clone()).collect();
+                    self.evaluate_statement(rest, &collect(others), k)
+                },
+                None => k(self),
+            };
+        };
+        for i in 0..items2.len() {
+            if used[i] {
+                continue;
+            }
+            used[i] = true;
+            let matched = self.evaluate_statement(x1, &items2[i], &mut |frame| frame.evaluate_unordered(s1, rest1, items2, used, collect, k));
+            used[i] = false;
+            if matched {
+                return true;
+            }
+        }
+        return false;
+    }
 
-            // {A,A} = {B,1} // ungrounded variable... fail.
-            // {A,A} = {A,1} // succeed?
+    pub fn evaluate_list(&mut self, l1 : &List, l2 : &List, k : &mut Continuation) -> bool {
+        if l1.spreads() > 0 || l2.spreads() > 0 {
+            return self.evaluate_list_spreads(l1, l2, k);
+        }
+        // a piped list [A, B | T] has the prefix A, B and the tail T, prefixes are matched element-wise and a tail is matched
+        // with whatever remains of the other list.
+        let (p1, t1) = l1.split();
+        let (p2, t2) = l2.split();
+        let n = p1.len().min(p2.len());
+        match (t1, t2) {
+            (None, None) => {
+                return self.evaluate_sequence(p1, p2, k);
+            },
+            (Some(t1), None) => {
+                if p1.len() > p2.len() {
+                    return false;
+                }
+                let rest = List::new(Sequence::new(p2[n..].to_vec()), false).as_statement(); // TODO this could also potentially be a UList?
+                return self.evaluate_sequence(p1, &p2[..n], &mut |frame| frame.evaluate_statement(t1, &rest, k));
+            },
+            (None, Some(_)) => {
+                return self.evaluate_list(l2, l1, k);
+            },
+            (Some(t1), Some(t2)) => {
+                if p1.len() < p2.len() {
+                    return self.evaluate_list(l2, l1, k);
+                }
+                // [A, B | T1] = [C | T2] matches A = C and [B | T1] = T2
+                let rest = if p1.len() == n { t1.clone() } else {
+                    List::new(Sequence::new(p1[n..].iter().chain(std::iter::once(t1)).cloned().collect()), true).as_statement()
+                };
+                return self.evaluate_sequence(&p1[..n], p2, &mut |frame| frame.evaluate_statement(&rest, t2, k));
+            },
+        }
+    }
 
+    fn evaluate_list_spreads(&mut self, l1 : &List, l2 : &List, k : &mut Continuation) -> bool {
+        // a spread *M captures a run of consecutive items, e.g. [First, *M, Last] = [1, 2, 3] matches M = [2]. A piped tail is
+        // treated as a spread at the end of the list. Only one of the lists may capture items.
+        if l1.spreads() == 0 {
+            return self.evaluate_list_spreads(l2, l1, k);
         }
+        if l2.spreads() > 0 {
+            return false; // both capture their remaining items
+        }
+        let items1 : Vec<Statement> = match l1.split() {
+            (items, Some(tail)) => items.iter().cloned().chain(std::iter::once(Statement::Spread(Box::new(tail.clone())))).collect(),
+            (items, None) => items.to_vec(),
+        };
+        return self.evaluate_spreads(&items1, &l2.items.items, k);
+    }
 
+    fn evaluate_spreads(&mut self, items1 : &[Statement], items2 : &[Statement], k : &mut Continuation) -> bool {
+        let fixed = items1.iter().filter(|x| !matches!(x, Statement::Spread(_))).count();
+        if fixed > items2.len() || (fixed == items1.len() && fixed != items2.len()) {
+            return false;
+        }
+        return match items1.split_first() {
+            None => k(self), // nothing left to match
+            Some((Statement::Spread(capture), s1)) => {
+                // try each of the runs that leave enough items for those that follow
+                for i in 0..=(items2.len() - fixed) {
+                    let run = List::new(Sequence::new(items2[..i].to_vec()), false).as_statement();
+                    if self.evaluate_statement(capture, &run, &mut |frame| frame.evaluate_spreads(s1, &items2[i..], k)) {
+                        return true;
+                    }
+                }
+                false
+            },
+            Some((x1, s1)) => self.evaluate_statement(x1, &items2[0], &mut |frame| frame.evaluate_spreads(s1, &items2[1..], k)),
+        };
+    }
 
-        // {A|B} = {1|[2]}  ?? TODO what is this behaviour...
-        // {A|B} = {1|{2}}  // recall that {A|{B}} = {A,B} in this case it may be better to unpack {1|{2}} 
-        
-        return empty();
+    pub fn evaluate_statement(&mut self, statement1 : &Statement, statement2 : &Statement, k : &mut Continuation) -> bool {
+        // variables that are already bound are replaced by their values.
+        if let Some(value) = self.value_of(statement1) {
+            return self.evaluate_statement(&value, statement2, k);
+        }
+        if let Some(value) = self.value_of(statement2) {
+            return self.evaluate_statement(statement1, &value, k);
+        }
+        return match (statement1, statement2) {
+            (Statement::Atom(atom1),    Statement::Atom(atom2))          => self.evaluate_primitive(atom1, atom2, k),
+            (Statement::Boolean(b1),    Statement::Boolean(b2))          => self.evaluate_primitive(b1, b2, k),
+            (Statement::Text(t1),       Statement::Text(t2))             => self.evaluate_primitive(t1, t2, k),
+            (Statement::Integer(_),     Statement::Integer(_))           => self.evaluate_numeric(statement1, statement2, k),
+            (Statement::Float(_),       Statement::Float(_))             => self.evaluate_numeric(statement1, statement2, k),
+            (Statement::Integer(_),     Statement::Float(_))             => self.evaluate_numeric(statement1, statement2, k),
+            (Statement::Float(_),       Statement::Integer(_))           => self.evaluate_numeric(statement1, statement2, k),
+            (Statement::Rational(_),    Statement::Integer(_) | Statement::Float(_) | Statement::Rational(_)) => self.evaluate_numeric(statement1, statement2, k),
+            (Statement::Integer(_) | Statement::Float(_), Statement::Rational(_))                          => self.evaluate_numeric(statement1, statement2, k),
+            (Statement::BinaryOperator(_) | Statement::UnaryOperator(_) | Statement::Call(_), _) => self.evaluate_expression(statement1, statement2, k),
+            (_, Statement::BinaryOperator(_) | Statement::UnaryOperator(_) | Statement::Call(_)) => self.evaluate_expression(statement2, statement1, k),
+            (Statement::Variable(var1), _)                               => self.evaluate_variable(var1, statement2, k),
+            (_,                         Statement::Variable(var2))       => self.evaluate_variable(var2, statement1, k),
+            (Statement::List(l1),       Statement::List(l2))             => self.evaluate_list(l1, l2, k),
+            (Statement::UList(l1),      Statement::UList(l2))            => self.evaluate_ulist(l1, l2, k),
+            (Statement::Object(o1),     Statement::Object(o2))           => self.evaluate_object(o1, o2, k),
+            (Statement::Sequence(s1),   Statement::Sequence(s2))         => self.evaluate_sequence(&s1.items, &s2.items, k),
+            _ => false, // e.g. statements of different kinds
+        };
     }
 
-    pub fn evaluate_list(l1: & List, l2: &List, frame: &Frame)  -> Box<dyn Iterator<Item = Frame>> {
-        // check if the lists are piped...
-        //println!("--{:?}", l1);
-        //println!("--{:?}", l2);
-        if (l1.ispiped && l2.ispiped) || (!l1.ispiped && !l2.ispiped) {
-            // evaluate these like a sequence :) 
-            return Frame::evaluate_sequence(&l1.items.as_ref(), &l2.items.as_ref(), frame);
-        } else if l1.ispiped && !l2.ispiped {
-            let piped_size = l1.len() - 1;
-            if piped_size > l2.len() {
-                println!("Failed to match, lengths: {:?} != {:?}", piped_size, l2.len()); // TODO debug
-                return empty(); // fail.
+    fn evaluate_variable(&mut self, variable : &Variable, statement : &Statement, k : &mut Continuation) -> bool {
+        if variable.is_anonymous() {
+            return k(self); // nothing changes...
+        } else if let Some(value) = self.value(variable) {
+            return self.evaluate_statement(&value, statement, k);
+        } else if let Statement::Variable(other) = statement {
+            if other.is_anonymous() || other == variable {
+                return k(self); // nothing changes...
             }
-            let l1items = l1.items.as_ref();
-            let l2items = l2.items.as_ref();
-            let (l1x, l1p) = l1items.split_at(piped_size); 
-            let (l2x, l2p) = l2items.split_at(piped_size);
-            let xiter = Frame::evaluate_sequence(&l1x, &l2x, frame);
-            let l2plist = List::new(l2p.to_vec(), false).as_statement(); // TODO this could also potentially be a UList?
-            return FrameGenerator::new_singlular(&l1p[0], &l2plist, xiter);
-        } else if !l1.ispiped && l2.ispiped {
-            let piped_size = l2.len() - 1;
-            if piped_size > l1.len() {
-                println!("Failed to match, lengths: {:?} != {:?}", l1.len(), piped_size); // TODO debug
-                return empty(); // fail.
+        }
+        let mark = self.mark();
+        // the statement may not be grounded, its variables are looked up in this frame. Collections share their items, so
+        // this does not copy them.
+        self.bind(variable, statement.clone());
+        if self.wake(variable, k) {
+            return true;
+        }
+        self.undo(mark);
+        return false;
+    }
+
+    /// evaluates a condition, `=` matches both sides, `X in L` matches `X` with each item of `L` and any other condition must 
+    /// evaluate to `true`. A condition that cannot be evaluated because some of its variables are unbound is suspended, it is 
+    /// evaluated again once they are bound.
+    pub fn evaluate_constraint(&mut self, statement : &Statement, k : &mut Continuation) -> bool {
+        // a conjunction holds if both of its conditions hold, each may bind variables used by the other
+        if let Statement::BinaryOperator(BinaryOperator::And(lhs, rhs)) = statement {
+            return self.evaluate_constraint(lhs, &mut |frame| frame.evaluate_constraint(rhs, k));
+        }
+        // a disjunction holds if either of its conditions hold, the second is tried if the first (or what follows it) fails
+        if let Statement::BinaryOperator(BinaryOperator::Or(lhs, rhs)) = statement {
+            return self.evaluate_constraint(lhs, k) || self.evaluate_constraint(rhs, k);
+        }
+        // index expressions are replaced by their elements first, the condition is suspended until their collections are bound
+        if statement.find(&is_index).is_some() {
+            let statement = self.substitute(statement);
+            if statement.find(&is_unbound_index).is_some() {
+                return self.suspend(statement, k);
             }
-            let l1items = l1.items.as_ref();
-            let l2items = l2.items.as_ref();
-            let (l1x, l1p) = l1items.split_at(piped_size); 
-            let (l2x, l2p) = l2items.split_at(piped_size);
-            let xiter = Frame::evaluate_sequence(&l1x, &l2x, frame);
-            let l1plist = List::new(l1p.to_vec(), false).as_statement(); // TODO this could also potentially be a UList?
-            return FrameGenerator::new_singlular(&l1plist, &l2p[0], xiter);
-        } 
-        unreachable!();
-    }   
-
-    pub fn evaluate_statement(statement1: &Statement, statement2 : &Statement, frame : &Frame) -> Box<dyn Iterator<Item = Frame>> {
-        // evaluate the statements. this should return an iterator of Frame objects.
-        // each frame Object should be combined with the current frame.
-        let result = match (statement1, statement2) {
-            (Statement::Atom(atom1),    Statement::Atom(atom2))          => Frame::evaluate_primitive(atom1, atom2),          // do nothing to the frame
-            (Statement::Integer(int1),  Statement::Integer(int2))        => Frame::evaluate_primitive(int1, int2),            // do nothing to the frame
-            (Statement::Float(float1),  Statement::Float(float2))        => Frame::evaluate_primitive(float1, float2),        // do nothing to the frame
-            (Statement::Variable(var1), _)                               => Frame::evaluate_variable(var1, statement2, &frame),
-            (_,                         Statement::Variable(var2))       => Frame::evaluate_variable(var2, statement1, &frame),
-            (Statement::List(l1),       Statement::List(l2))             => Frame::evaluate_list(l1, l2, &frame),
-            (Statement::UList(l1),      Statement::UList(l2))            => Frame::evaluate_ulist(l1, l2, &frame),
-
-            (s1, s2) => {println!("Failed to match {:?} {:?}", s1, s2); return empty()}
+            return self.resolve(&statement, &mut |frame, condition| frame.evaluate_constraint(condition, k));
+        }
+        if let Statement::BinaryOperator(BinaryOperator::Equal(lhs, rhs)) = statement {
+            return self.evaluate_statement(lhs, rhs, k);
+        }
+        if let Statement::BinaryOperator(BinaryOperator::In(item, collection)) = statement {
+            return self.evaluate_member(item, collection, statement, k);
+        }
+        let condition = self.substitute(statement);
+        return match condition.evaluate(&self.numbers) {
+            Ok(Statement::Boolean(Boolean(true))) => k(self),
+            Ok(Statement::Boolean(Boolean(false))) => false,
+            Ok(value) => self.abort(EvaluationError::NotBoolean(value)),
+            Err(EvaluationError::Unbound(_)) => self.suspend(condition, k),
+            Err(error) => self.abort(error),
         };
-        return result;
-    } 
+    }
 
-    
-    fn evaluate_variable(variable: &Variable, statement: &Statement, frame : &Frame) -> Box<dyn Iterator<Item = Frame>> {
-        if variable.is_anonymous() {
-            return singleton(Frame::new()); // nothing changes...
-        } else if let Some(value) = frame.get(variable) {
-           if value == statement {
-                return singleton(Frame::new()); // nothing changes...
-            } else if let Statement::Variable(othervar) = value {
-                let iter = Frame::evaluate_variable(othervar, statement, frame);
-                
-
-                return empty();
-            } else {
-                println!("Variable {:?} was already grounded {:?} but didn't match {:?}", variable, value, statement);
-                return empty();
-           }
-        } else {
-            let mut new_frame = Frame::new();
-            new_frame.insert(variable, statement.clone()); // TODO we are assuming that the statement is grounded...
-            return singleton(new_frame);
+    // matches `item` with each item of `collection` (or each key if it is an object), the condition is suspended until the 
+    // collection is bound.
+    fn evaluate_member(&mut self, item : &Statement, collection : &Statement, condition : &Statement, k : &mut Continuation) -> bool {
+        let collection = match self.substitute(collection) {
+            x @ (Statement::BinaryOperator(_) | Statement::UnaryOperator(_) | Statement::Call(_)) => match x.evaluate(&self.numbers) {
+                Ok(value) => value,
+                Err(EvaluationError::Unbound(_)) => x,
+                Err(error) => return self.abort(error),
+            },
+            x => x,
+        };
+        let Some(items) = members(&collection) else {
+            if collection.is_ground() {
+                return self.abort(EvaluationError::TypeMismatch { operator : "in", operands : vec![self.substitute(item), collection] });
+            }
+            return self.suspend(self.substitute(condition), k);
+        };
+        for x in items {
+            if self.evaluate_statement(item, x, k) {
+                return true;
+            }
         }
+        return false;
     }
 
-    /// this matches primitive types
-    fn evaluate_primitive<T : PartialEq + std::fmt::Debug>(arg1 : &T, arg2 : &T) -> Box<dyn Iterator<Item = Frame>>{
-        if arg1 == arg2 {
-            return singleton(Frame::new());
-        } else {
-            println!("Failed to match {:?} {:?}", arg1, arg2); // TODO better debug info
-            return empty();
-        } 
-    } 
-}
-
-pub fn empty<'a, T>() -> Box<dyn Iterator<Item = T> + 'a> where T : 'a {
-    let v: Vec<T> = Vec::new();
-    Box::new(v.into_iter())
-}
+    fn suspend(&mut self, statement : Statement, k : &mut Continuation) -> bool {
+        let mark = self.mark();
+        let slots = variables(&statement);
+        self.constraints.push(Constraint { statement : Rc::new(statement), slots : slots, active : true });
+        self.trail.push(Trail::Suspend);
+        if k(self) {
+            return true;
+        }
+        self.undo(mark);
+        return false;
+    }
 
-pub fn singleton<'a, T>(value : T) -> Box<dyn Iterator<Item=T> + 'a> where T : 'a {
-    let mut v = Vec::<T>::new();
-    v.push(value);
-    return Box::new(v.into_iter());
-}
+    // evaluates the suspended constraints on `variable` (which has just been bound), the constraints are not undone by this 
+    // method, the caller should undo to a mark made before binding `variable`.
+    fn wake(&mut self, variable : &Variable, k : &mut Continuation) -> bool {
+        let woken : Vec<usize> = self.constraints.iter().enumerate()
+            .filter(|(_, c)| c.active && variable.slot.is_some_and(|slot| c.slots.contains(&slot)))
+            .map(|(i, _)| i)
+            .collect();
+        return self.evaluate_woken(&woken, k);
+    }
 
-// used to match sequences
-pub struct FrameGenerator {
-    parent_frames : Box<dyn Iterator<Item=Frame>>,
-    current_parent_frame : Option<Frame>,
-    frames : Box<dyn Iterator<Item=Frame>>,
-    x1: Statement,
-    x2: Statement,
-}
+    fn evaluate_woken(&mut self, woken : &[usize], k : &mut Continuation) -> bool {
+        let Some((i, rest)) = woken.split_first() else {
+            return k(self);
+        };
+        // the constraint is replaced by its evaluation, which may suspend it again (e.g. if it still has unbound variables).
+        self.constraints[*i].active = false;
+        self.trail.push(Trail::Wake(*i));
+        let statement = Rc::clone(&self.constraints[*i].statement);
+        return self.evaluate_constraint(&statement, &mut |frame| frame.evaluate_woken(rest, k));
+    }
 
-impl FrameGenerator {
+    /// the value of `statement` once its variables are replaced by their values, see `Statement::evaluate`.
+    pub fn evaluate(&self, statement : &Statement) -> Result<Statement, EvaluationError> {
+        return self.substitute(statement).evaluate(&self.numbers);
+    }
 
-    pub fn new_singlular(s1 : &Statement, s2 : &Statement, mut parent_frames : Box<dyn Iterator<Item=Frame>>) -> Box<dyn Iterator<Item=Frame>> {
-        let current_parent_frame = parent_frames.next();
-        if current_parent_frame.is_none() { // no parent frame was avaliable... a match failed immediately 
-            return empty();
+    /// replaces each index expression (e.g. `P[I]` or `P.key`) in `statement` with the element that it refers to, calling `k` 
+    /// for each way of doing so. An unbound index enumerates the positions (or keys) of its collection, an index that is absent fails.
+    pub fn resolve(&mut self, statement : &Statement, k : &mut Resolved) -> bool {
+        if statement.find(&is_index).is_none() {
+            return k(self, statement);
         }
-        let current_parent_frame_unwrapped = current_parent_frame.unwrap();
+        let statement = self.substitute(statement);
+        // indices are resolved innermost first, e.g. the index Q[0] in P[Q[0]]
+        let innermost = |x : &Statement| is_index(x) && x.children().iter().all(|y| y.find(&is_index).is_none());
+        let Some(index @ Statement::BinaryOperator(BinaryOperator::Index(collection, key))) = statement.find(&innermost) else {
+            unreachable!()
+        };
+        return self.evaluate_index(collection, key, &mut |frame, element| {
+            let statement = statement.replace(index, element);
+            frame.resolve(&statement, k)
+        });
+    }
 
-        let frames = Frame::evaluate_statement(&s1, &s2, &current_parent_frame_unwrapped);
-        
-        return Box::new(FrameGenerator { x1 : s1.clone(), x2 : s2.clone(), 
-                            current_parent_frame : Some(current_parent_frame_unwrapped), 
-                            frames : frames, 
-                            parent_frames : parent_frames });  
+    fn evaluate_index(&mut self, collection : &Statement, key : &Statement, k : &mut Resolved) -> bool {
+        let items = match collection {
+            Statement::List(x) if x.spreads() == 0 => &x.items,
+            Statement::Sequence(x) => x,
+            Statement::Object(x) => {
+                // entries are (key, value) sequences
+                for entry in x.0.0.iter() {
+                    if let Statement::Sequence(entry) = entry {
+                        if self.evaluate_statement(key, &entry[0], &mut |frame| k(frame, &entry[1])) {
+                            return true;
+                        }
+                    }
+                }
+                return false;
+            },
+            _ => return false, // not a collection that can be indexed
+        };
+        if let Statement::Integer(Integer(i)) = key { // the common case, a single position
+            return match usize::try_from(*i).ok().and_then(|i| items.items.get(i)) {
+                Some(item) => k(self, item),
+                None => false,
+            };
+        }
+        for (i, item) in items.iter().enumerate() {
+            if self.evaluate_statement(key, &(i as IntegerValue).as_statement(), &mut |frame| k(frame, item)) {
+                return true;
+            }
+        }
+        return false;
     }
 
-    pub fn new(seq1: &[Statement], seq2: &[Statement], mut parent_frames : Box<dyn Iterator<Item=Frame>>) -> Box<dyn Iterator<Item=Frame>> {
-        if seq1.len() == 0 || seq2.len() == 0 {
-            panic!("Invalid use of FrameGenerator, sequences must not be empty.");
+    /// this matches an arithmetic expression (e.g. `N + 1`) or a function call with a value, the expression is evaluated if it 
+    /// can be evaluated, otherwise it is solved for its variable.
+    fn evaluate_expression(&mut self, expression : &Statement, value : &Statement, k : &mut Continuation) -> bool {
+        let expression = self.substitute(expression);
+        match expression.evaluate(&self.numbers) {
+            Ok(expression) => return self.evaluate_statement(&expression, value, k),
+            Err(EvaluationError::Unbound(_)) => {},
+            Err(error) => return self.abort(error),
         }
-        if seq1.len() != seq2.len() {
-            panic!("Invalid use of FrameGenerator, sequences must be the same length: {:?} != {:?}", seq1.len(), seq2.len());
+        let value = self.substitute(value);
+        if let Some((variable, solution)) = invert(&expression, &value, &self.numbers) {
+            return self.evaluate_variable(variable, &solution, k);
         }
-        
-        let (x1, s1) = seq1.split_at(1);
-        let (x2, s2) = seq2.split_at(1);
+        if !value.is_ground() || variables(&expression).len() > 1 || non_invertible(&expression).is_some() {
+            // the expression cannot be solved until more of its variables are bound
+            return self.suspend(Statement::BinaryOperator(BinaryOperator::Equal(Box::new(expression), Box::new(value))), k);
+        }
+        return false;
+    }
 
-        let current_parent_frame = parent_frames.next();
-        if current_parent_frame.is_none() { // no parent frame was avaliable... a match probably failed immediately 
-            return empty();
+    /// this matches numbers according to the frames `NumericEquality` policy.
+    fn evaluate_numeric(&mut self, arg1 : &Statement, arg2 : &Statement, k : &mut Continuation) -> bool {
+        if self.numbers.equality.numeric(arg1, arg2) == Some(true) {
+            return k(self);
         }
-        let current_parent_frame_unwrapped = current_parent_frame.unwrap();
+        return false;
+    }
 
-        let frames = Frame::evaluate_statement(&x1[0], &x2[0], &current_parent_frame_unwrapped);
-        
-        let current = Box::new(FrameGenerator { x1 : x1[0].clone(), x2 : x2[0].clone(), 
-                                                current_parent_frame : Some(current_parent_frame_unwrapped), 
-                                                frames : frames, 
-                                                parent_frames : parent_frames });            
-        if s1.len() > 0 { 
-            return FrameGenerator::new(s1, s2, current);
-        } else {
-            return current; // end of sequence reached.
+    /// this matches primitive types
+    fn evaluate_primitive<T : PartialEq>(&mut self, arg1 : &T, arg2 : &T, k : &mut Continuation) -> bool {
+        if arg1 == arg2 {
+            return k(self);
         }
+        return false;
+    }
+
+    fn value_of(&self, statement : &Statement) -> Option<Rc<Statement>> {
+        if let Statement::Variable(variable) = statement {
+            return self.value(variable);
+        }
+        return None;
     }
 }
 
-impl Iterator for FrameGenerator {
-    type Item = Frame;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let frame = self.frames.next();
-        if frame.is_none() { // cannot produce any more frames with this parent frame, try next one.
-            self.current_parent_frame = self.parent_frames.next();
-            if self.current_parent_frame.is_none() {
-                return None; // no more parent frames, cannot proceed.
-            }
-            // evaluate statement should also return some debug information? i.e. whether the match failed or not.
-            self.frames = Frame::evaluate_statement(&self.x1, &self.x2, self.current_parent_frame.as_ref().unwrap());
-            return self.next();
+// the slots of the (named) variables in `statement`.
+fn variables(statement : &Statement) -> Vec<usize> {
+    let mut slots = Vec::new();
+    let mut stack = vec![statement];
+    while let Some(x) = stack.pop() {
+        match x {
+            Statement::Variable(Variable { slot : Some(slot), .. }) if !slots.contains(slot) => slots.push(*slot),
+            x => stack.extend(x.children()),
         }
-        // join parent frame with this frame, these cannot be None
-        let current_parent_frame_unwrapped = self.current_parent_frame.as_ref().unwrap();
-        let mut frame_unwrapped = frame.unwrap();
-        frame_unwrapped.join(current_parent_frame_unwrapped);
-        //println!("--{:?}", frame_unwrapped);
-        return Some(frame_unwrapped);
     }
+    return slots;
 }
 
+fn is_index(statement : &Statement) -> bool {
+    return matches!(statement, Statement::BinaryOperator(BinaryOperator::Index(_, _)));
+}
 
+// whether `statement` is an index expression whose collection is an unbound variable (e.g. `P[0]` before `P` is bound)
+fn is_unbound_index(statement : &Statement) -> bool {
+    return matches!(statement, Statement::BinaryOperator(BinaryOperator::Index(collection, _)) if matches!(**collection, Statement::Variable(_)));
+}
 
+// splits the items of an unordered collection into those that are matched individually and the capture of the remaining items.
+fn unordered(items : &Sequence, ispiped : bool) -> (Vec<Statement>, Option<Statement>) {
+    let mut result = Vec::with_capacity(items.len());
+    let mut rest = None;
+    for (i, item) in items.iter().enumerate() {
+        match item {
+            Statement::Spread(x) => rest = Some((**x).clone()),
+            x if ispiped && i == items.len() - 1 => rest = Some(x.clone()),
+            x => result.push(x.clone()),
+        }
+    }
+    return (result, rest);
+}
 
 
+/// Holds the values of variables while matching, indexed by their slot. Bindings are recorded on a trail so that extending
+/// a frame is cheap and backtracking only needs to undo the bindings made since some earlier `mark`. The frame also holds
+/// conditions that are suspended until their variables are bound.
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    slots: Vec<Option<Rc<Statement>>>, // value of variables, shared with the statements that refer to them
+    trail: Vec<Trail>,              // changes in the order that they were made
+    constraints: Vec<Constraint>,   // suspended conditions
+    error: Option<Failure>,         // the error that stopped matching
+    pub numbers : Numbers,          // how numbers are compared when matching and divided when evaluating
+}
 
+#[derive(Debug, Clone)]
+enum Trail {
+    Bind(usize),    // a slot was bound
+    Suspend,        // a constraint was suspended (it is the last constraint)
+    Wake(usize),    // a constraint was woken
+}
 
 #[derive(Debug, Clone)]
-pub struct Frame {
-    map: HashMap<String, Statement>, // value of variables
+struct Constraint {
+    statement : Rc<Statement>,
+    slots : Vec<usize>, // the variables that wake the constraint when bound
+    active : bool,
 }
 
 impl Frame {
-    // Create a new instance of Frame
-    pub fn new() -> Self {
+    // Create a new instance of Frame for a goal with `size` variables
+    pub fn new(size : usize) -> Self {
+        Frame::with_numbers(size, Numbers::default())
+    }
+
+    // Create a new instance of Frame for a goal with `size` variables that computes with numbers according to `numbers`
+    pub fn with_numbers(size : usize, numbers : Numbers) -> Self {
         Frame {
-            map: HashMap::new(),
+            slots: vec![None; size],
+            trail: Vec::new(),
+            constraints: Vec::new(),
+            error: None,
+            numbers : numbers,
+        }
+    }
+
+    // Replace each grounded variable in `statement` with its value, unbound variables are left as they are. A statement
+    // without bound variables is shared rather than rebuilt.
+    pub fn substitute(&self, statement : &Statement) -> Statement {
+        if statement.find(&|x| matches!(x, Statement::Variable(y) if self.get(y).is_some())).is_none() {
+            return statement.clone();
         }
+        return match statement {
+            Statement::Variable(x) => match self.get(x) {
+                Some(value) => self.substitute(value),
+                None => statement.clone(),
+            },
+            x => x.map(|y| self.substitute(y)).splice(), // captures that are bound are spliced into their collection
+        };
+    }
+
+    // Bind an (unbound) variable to a value
+    pub fn bind<S : Into<Rc<Statement>>>(&mut self, key: &Variable, value: S) {
+        if let Some(slot) = key.slot {
+            if slot >= self.slots.len() {
+                self.slots.resize(slot + 1, None);
+            }
+            self.slots[slot] = Some(value.into());
+            self.trail.push(Trail::Bind(slot));
+        }
+    }
+
+    // The current position on the trail, bindings made after this point are removed by `undo`
+    pub fn mark(&self) -> usize {
+        return self.trail.len();
     }
 
-    pub fn join(&mut self, other : &Frame) {
-        for (key, value) in other.map.iter() {
-            self.map.insert(key.clone(), value.clone());
+    // Remove all bindings (and suspended constraints) made since `mark`
+    pub fn undo(&mut self, mark : usize) {
+        for change in self.trail.drain(mark..).rev() {
+            match change {
+                Trail::Bind(slot) => self.slots[slot] = None,
+                Trail::Suspend => { self.constraints.pop(); },
+                Trail::Wake(i) => self.constraints[i].active = true,
+            }
         }
     }
 
-    // Insert a key-value pair into the map
-    pub fn insert(&mut self, key: &Variable, value: Statement) {
-        self.map.insert(key.name.clone(), value);
+    // Stop matching because of `error`, matching stops as if the current match was accepted (returning `true`), whoever 
+    // started matching should then check for the error with `take_error`.
+    pub fn abort<E : Into<Failure>>(&mut self, error : E) -> bool {
+        self.error = Some(error.into());
+        return true;
+    }
+
+    // Records that the error that stopped matching (if there is one) happened while evaluating what is written at `span`,
+    // unless it is already known where
+    pub fn locate(&mut self, span : &Span) {
+        if let Some(error) = self.error.as_mut() {
+            error.locate(span);
+        }
+    }
+
+    // The error that stopped matching, if there was one
+    pub fn take_error(&mut self) -> Option<Failure> {
+        return self.error.take();
+    }
+
+    // The conditions that are suspended waiting for their variables to be bound
+    pub fn suspended(&self) -> impl Iterator<Item = &Statement> {
+        return self.constraints.iter().filter(|c| c.active).map(|c| &*c.statement);
     }
 
     // Get the value associated with a key
     pub fn get(&self, key: &Variable) -> Option<&Statement> {
-        return self.map.get(key.name.as_str());
+        return self.slots.get(key.slot?)?.as_deref();
     }
 
-    // Remove a key-value pair from the map
-    pub fn remove(&mut self, key: &Variable) -> Option<Statement> {
-        self.map.remove(&key.name)
+    // The value associated with a key, shared rather than copied
+    fn value(&self, key: &Variable) -> Option<Rc<Statement>> {
+        return self.slots.get(key.slot?)?.clone();
     }
 
-    // Check if the map is empty
+    // Check if no variables are bound
     pub fn is_empty(&self) -> bool {
-        self.map.is_empty()
+        self.len() == 0
     }
 
-    // Get the number of elements in the map
+    // Get the number of bound variables
     pub fn len(&self) -> usize {
-        self.map.len()
+        self.slots.iter().filter(|x| x.is_some()).count()
     }
+}
 
-    // Clear all key-value pairs from the map
-    pub fn clear(&mut self) {
-        self.map.clear();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::statement::ErrorKind;
+    use crate::interpret::tests::expressions;
+
+    // each way in which `condition` holds (as a rule would evaluate it), written as `answer` with its variables replaced by
+    // their values. Ways that leave a condition suspended do not count, as they do not count for a rule.
+    fn solutions(answer : &str, condition : &str) -> Vec<String> {
+        let (statements, symbols) = expressions(&[answer, condition]);
+        let mut frame = Frame::new(0);
+        let mut solutions = Vec::new();
+        frame.evaluate_constraint(&statements[1], &mut |frame| {
+            if frame.suspended().next().is_none() {
+                solutions.push(symbols.display(&frame.substitute(&statements[0])).to_string());
+            }
+            return false;
+        });
+        if let Some(failure) = frame.take_error() {
+            panic!("{}", failure.error.message(&symbols));
+        }
+        return solutions;
     }
-}
 
+    #[test]
+    fn evaluation_errors() {
+        let (statements, symbols) = expressions(&["X", "P", "[1, 2]", "X + 1", "Y * 2", "X + a", "X / 0", "X * 9223372036854775807", "P[2]", "len(X)"]);
+        let variable = |x : &Statement| match x { Statement::Variable(x) => *x, x => panic!("{:?}", x) };
+        let mut frame = Frame::new(0);
+        frame.bind(&variable(&statements[0]), Statement::Integer(Integer(2)));
+        frame.bind(&variable(&statements[1]), statements[2].clone());
+        assert_eq!(symbols.display(&frame.evaluate(&statements[3]).unwrap()).to_string(), "3");
+        let errors : Vec<EvaluationError> = statements[4..].iter().map(|x| frame.evaluate(x).unwrap_err()).collect();
+        assert!(matches!(&errors[0], EvaluationError::Unbound(x) if symbols.name(x.name) == "Y"), "{:?}", errors[0]);
+        assert!(matches!(&errors[1], EvaluationError::TypeMismatch { operator : "+", .. }), "{:?}", errors[1]);
+        assert!(matches!(&errors[2], EvaluationError::DivisionByZero(_)), "{:?}", errors[2]);
+        assert!(matches!(&errors[3], EvaluationError::Overflow(_)), "{:?}", errors[3]);
+        assert!(matches!(&errors[4], EvaluationError::NoElement(_)), "{:?}", errors[4]);
+        assert!(matches!(&errors[5], EvaluationError::TypeMismatch { operator : "len", .. }), "{:?}", errors[5]);
+        let kinds : Vec<ErrorKind> = errors.iter().map(|x| x.kind()).collect();
+        assert_eq!(kinds, [ErrorKind::Unbound, ErrorKind::Type, ErrorKind::Arithmetic, ErrorKind::Arithmetic, ErrorKind::Type, ErrorKind::Type]);
+        // an unbound variable may be bound later, the expression can then be evaluated
+        frame.bind(&variable(statements[4].children()[0]), Statement::Integer(Integer(4)));
+        assert_eq!(symbols.display(&frame.evaluate(&statements[4]).unwrap()).to_string(), "8");
+    }
+
+    #[test]
+    fn backtracking_undoes_bindings() {
+        assert_eq!(solutions("[A, B]", "[A, B] = [1, 2]"), ["[1, 2]"]);
+        assert_eq!(solutions("A", "[A, A] = [1, 2]"), Vec::<String>::new());
+        assert_eq!(solutions("[A, B]", "(A = 1 || A = 2) && [A, B] = [2, 3]"), ["[2, 3]"]);
+        assert_eq!(solutions("A", "A in [1, 2, 3] && A > 1"), ["2", "3"]);
+    }
 
+    #[test]
+    fn spread_captures() {
+        assert_eq!(solutions("[F, M, L]", "[F, *M, L] = [1, 2, 3, 4]"), ["[1, [2, 3], 4]"]);
+        assert_eq!(solutions("[M, N]", "[*M, *N] = [1, 2]"), ["[[], [1, 2]]", "[[1], [2]]", "[[1, 2], []]"]);
+        assert_eq!(solutions("M", "[1, *M, 2] = [1]"), Vec::<String>::new());
+        assert_eq!(solutions("[A, R]", "{A, *R} = {1, 2}"), ["[1, {2}]", "[2, {1}]"]);
+        assert_eq!(solutions("[V, R]", "{a : V, *R} = {b : 2, a : 1}"), ["[1, {b : 2}]"]);
+        // only one side may capture the remaining items
+        assert_eq!(solutions("[A, B]", "[*A] = [*B]"), Vec::<String>::new());
+        assert_eq!(solutions("[A, B]", "{1, *A} = {1, *B}"), Vec::<String>::new());
+    }
 
+    #[test]
+    fn index_expressions() {
+        assert_eq!(solutions("X", "[1, 2, 3][1] = X"), ["2"]);
+        assert_eq!(solutions("I", "[1, 2, 1][I] = 1"), ["0", "2"]);
+        assert_eq!(solutions("K", "{a : 1, b : 2}[K] = 2"), ["b"]);
+        assert_eq!(solutions("X", "{a : [1, 2]}.a[0] = X"), ["1"]);
+        assert_eq!(solutions("X", "[[1, 2], [3]][1][0] = X"), ["3"]);
+        // an index that is absent fails
+        assert_eq!(solutions("X", "[1, 2][2] = X"), Vec::<String>::new());
+        assert_eq!(solutions("X", "{a : 1}.b = X"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn index_waits_for_its_collection() {
+        assert_eq!(solutions("X", "P[0] = X && P = [2, 3]"), ["2"]);
+        assert_eq!(solutions("P", "P[0] > 1 && P = [2, 3]"), ["[2, 3]"]);
+        assert_eq!(solutions("P", "P[0] > 2 && P = [2, 3]"), Vec::<String>::new());
+        assert_eq!(solutions("I", "P[Q[0]] = 3 && Q = [I] && P = [2, 3]"), ["1"]);
+        // a collection that is never bound leaves the condition suspended
+        assert_eq!(solutions("X", "P[0] = X"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn bindings_are_shared() {
+        let (statements, _) = expressions(&["X", "[1, [2, 3]]", "[X, 4]"]);
+        let Statement::Variable(x) = statements[0] else { unreachable!() };
+        let items = |x : &Statement| match x { Statement::List(y) => Rc::clone(&y.items.items), _ => unreachable!() };
+        let mut frame = Frame::new(1);
+        // a statement without bound variables is not rebuilt
+        assert!(Rc::ptr_eq(&items(&frame.substitute(&statements[1])), &items(&statements[1])));
+        frame.bind(&x, statements[1].clone());
+        assert!(Rc::ptr_eq(&items(&frame.substitute(&statements[0])), &items(&statements[1])));
+        let substituted = frame.substitute(&statements[2]);
+        assert!(Rc::ptr_eq(&items(&items(&substituted)[0]), &items(&statements[1])));
+    }
+
+    #[test]
+    fn unordered_matches_each_item_once() {
+        assert_eq!(solutions("[A, B]", "{A, B} = {1, 2}"), ["[1, 2]", "[2, 1]"]);
+        assert_eq!(solutions("[A, R]", "{A, 1, *R} = {1, 2, 3}"), ["[2, {3}]", "[3, {2}]"]);
+        assert_eq!(solutions("A", "{A, A} = {1, 2}"), Vec::<String>::new());
+        assert_eq!(solutions("R", "{1, 1, *R} = {1, 2}"), Vec::<String>::new());
+    }
 
-impl Default for Frame {
-    fn default() -> Self {
-        Self { map: Default::default() }
+    #[test]
+    fn undo_to_mark() {
+        let (statements, _) = expressions(&["[A, B]"]);
+        let variables : Vec<Variable> = statements[0].children().into_iter().map(|x| match x { Statement::Variable(x) => *x, _ => unreachable!() }).collect();
+        let mut frame = Frame::new(2);
+        frame.bind(&variables[0], Integer(1).as_statement());
+        let mark = frame.mark();
+        frame.bind(&variables[1], Integer(2).as_statement());
+        assert_eq!(frame.len(), 2);
+        frame.undo(mark);
+        assert_eq!(frame.get(&variables[0]), Some(&Integer(1).as_statement()));
+        assert_eq!(frame.get(&variables[1]), None);
     }
 }
@@ -0,0 +1,68 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use crate::statement::{Statement, Sequence, AsStatement, EvaluationError, Numbers};
+
+/// The signature of a function over statements, it is given the values of its arguments.
+pub type Implementation = dyn Fn(&[Statement]) -> Result<Statement, EvaluationError> + Send + Sync;
+
+/// A named function that rules may call, either as `name(X, Y)` or (if it is an infix operator) as `X name Y`.
+#[derive(Clone)]
+pub struct Function {
+    pub name : Arc<str>,
+    pub arity : usize,
+    pub infix : Option<u8>,                 // the precedence of a function written between its two arguments
+    implementation : Arc<Implementation>,
+}
+
+/// An application of a function to its arguments.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Call {
+    pub function : Function,
+    pub arguments : Sequence,
+}
+
+impl Function {
+    pub fn new<S : Into<Arc<str>>>(name : S, arity : usize, infix : Option<u8>, implementation : Arc<Implementation>) -> Self {
+        return Function { name : name.into(), arity : arity, infix : infix, implementation : implementation };
+    }
+
+    /// applies this function to (the values of) `arguments`, of which there are `arity`.
+    pub fn apply(&self, arguments : &[Statement]) -> Result<Statement, EvaluationError> {
+        return (self.implementation)(arguments);
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.name, self.arity)
+    }
+}
+
+// functions are the same if they were registered together, the implementation is compared by address.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        return self.name == other.name && self.arity == other.arity && self.infix == other.infix && Arc::ptr_eq(&self.implementation, &other.implementation);
+    }
+}
+
+impl Eq for Function {}
+
+impl Hash for Function {
+    fn hash<H : Hasher>(&self, state : &mut H) {
+        (&self.name, self.arity, self.infix).hash(state);
+    }
+}
+
+impl Call {
+    pub fn evaluate(&self, numbers : &Numbers) -> Result<Statement, EvaluationError> {
+        let arguments = self.arguments.iter().map(|x| x.evaluate(numbers)).collect::<Result<Vec<_>, _>>()?;
+        return self.function.apply(&arguments);
+    }
+}
+
+impl AsStatement for Call {
+    fn as_statement(self) -> Statement {
+        return Statement::Call(self);
+    }
+}
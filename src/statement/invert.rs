@@ -0,0 +1,164 @@
+use crate::statement::{Statement, Variable, BinaryOperator, UnaryOperator, Integer, Float, Numbers, Division};
+
+// Arithmetic expressions such as `N + 1` may appear in goal heads, they are matched against a number by solving for the
+// variable that they contain. Only linear expressions in a single variable are supported, these are built from constants
+// with `+`, `-`, `*` and negation (e.g. `2 * N - 1`).
+
+/// finds the first expression in `statement` that cannot be solved for its variable, if there is one.
+pub fn non_invertible(statement : &Statement) -> Option<&Statement> {
+    return match statement {
+        Statement::Call(_) => Some(statement),
+        Statement::BinaryOperator(_) | Statement::UnaryOperator(_) => if invertible(statement) { None } else { Some(statement) },
+        x => x.children().into_iter().find_map(non_invertible),
+    };
+}
+
+/// replaces each constant expression in `statement` with its value, e.g. `[N + (1 + 2)]` becomes `[N + 3]`.
+pub fn fold(statement : &Statement) -> Statement {
+    if is_constant(statement) {
+        if let Some(value) = constant(statement) { // errors are left for evaluation to report
+            return value;
+        }
+    }
+    return statement.map(fold);
+}
+
+/// solves `expression = value` for the variable in `expression`, fails if `value` is not a number or there is no solution.
+/// Its constants are evaluated according to `numbers`.
+pub fn invert<'a>(expression : &'a Statement, value : &Statement, numbers : &Numbers) -> Option<(&'a Variable, Statement)> {
+    if !is_number(value) {
+        return None;
+    }
+    return match expression {
+        Statement::Variable(x) if !x.is_anonymous() => Some((x, value.clone())),
+        Statement::BinaryOperator(BinaryOperator::Add(x, y)) => {
+            if is_constant(x) {
+                invert(y, &BinaryOperator::subtract(value, &x.evaluate(numbers).ok()?).ok()?, numbers)
+            } else if is_constant(y) {
+                invert(x, &BinaryOperator::subtract(value, &y.evaluate(numbers).ok()?).ok()?, numbers)
+            } else {
+                None
+            }
+        },
+        Statement::BinaryOperator(BinaryOperator::Subtract(x, y)) => {
+            if is_constant(x) {
+                invert(y, &BinaryOperator::subtract(&x.evaluate(numbers).ok()?, value).ok()?, numbers)
+            } else if is_constant(y) {
+                invert(x, &BinaryOperator::add(value, &y.evaluate(numbers).ok()?).ok()?, numbers)
+            } else {
+                None
+            }
+        },
+        Statement::BinaryOperator(BinaryOperator::Multiply(x, y)) => {
+            if is_factor(x) {
+                invert(y, &divide_exact(value, &x.evaluate(numbers).ok()?)?, numbers)
+            } else if is_factor(y) {
+                invert(x, &divide_exact(value, &y.evaluate(numbers).ok()?)?, numbers)
+            } else {
+                None
+            }
+        },
+        Statement::UnaryOperator(UnaryOperator::Minus(x)) => invert(x, &UnaryOperator::negate(value).ok()?, numbers),
+        _ => None,
+    };
+}
+
+fn invertible(expression : &Statement) -> bool {
+    if is_constant(expression) {
+        return true;
+    }
+    return match expression {
+        Statement::Variable(x) => !x.is_anonymous(),
+        Statement::BinaryOperator(BinaryOperator::Add(x, y)) |
+        Statement::BinaryOperator(BinaryOperator::Subtract(x, y)) => (is_constant(x) && invertible(y)) || (invertible(x) && is_constant(y)),
+        Statement::BinaryOperator(BinaryOperator::Multiply(x, y)) => (is_factor(x) && invertible(y)) || (invertible(x) && is_factor(y)),
+        Statement::UnaryOperator(UnaryOperator::Minus(x)) => invertible(x),
+        _ => false,
+    };
+}
+
+/// whether `expression` is a number or an arithmetic expression containing only numbers.
+fn is_constant(expression : &Statement) -> bool {
+    return match expression {
+        Statement::Integer(_) | Statement::Float(_) => true,
+        Statement::BinaryOperator(BinaryOperator::Add(x, y)) |
+        Statement::BinaryOperator(BinaryOperator::Subtract(x, y)) |
+        Statement::BinaryOperator(BinaryOperator::Multiply(x, y)) => is_constant(x) && is_constant(y),
+        Statement::BinaryOperator(BinaryOperator::Divide(x, y)) => is_constant(x) && is_factor(y),
+        Statement::UnaryOperator(UnaryOperator::Minus(x)) => is_constant(x),
+        _ => false,
+    };
+}
+
+/// whether `expression` is a constant that can be divided by.
+fn is_factor(expression : &Statement) -> bool {
+    return is_constant(expression) && !matches!(constant(expression), Some(Statement::Integer(Integer(0)) | Statement::Float(Float(0.0))) | None);
+}
+
+// the value of a constant expression, arithmetic does not compare numbers so any equality policy will do. A division whose
+// value depends on how the program divides integers (e.g. `1 / 2`) is not constant, the program chooses that when it runs.
+fn constant(expression : &Statement) -> Option<Statement> {
+    let truncated = expression.evaluate(&Numbers::default()).ok()?;
+    let exact = expression.evaluate(&Numbers::rational()).ok()?;
+    return if truncated == exact { Some(truncated) } else { None };
+}
+
+fn is_number(statement : &Statement) -> bool {
+    return matches!(statement, Statement::Integer(_) | Statement::Float(_) | Statement::Rational(_));
+}
+
+/// divides `value` by `factor`, integer division must have no remainder.
+fn divide_exact(value : &Statement, factor : &Statement) -> Option<Statement> {
+    return match (value, factor) {
+        (Statement::Integer(x), Statement::Integer(y)) if x.0 % y.0 != 0 => None,
+        _ => BinaryOperator::divide(value, factor, Division::Truncate).ok(), // there is no remainder to truncate
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpret::tests::expression;
+    use crate::statement::AsStatement;
+
+    // the value that the variable in `source` must have for `source` to equal `value`, e.g. `N = 4`.
+    fn solve(source : &str, value : Statement) -> Option<String> {
+        let (statement, symbols) = expression(source);
+        let (variable, solution) = invert(&statement, &value, &Numbers::default())?;
+        return Some(format!("{} = {}", symbols.name(variable.name), symbols.display(&solution)));
+    }
+
+    #[test]
+    fn solves_for_the_variable() {
+        assert_eq!(solve("N + 1", 5.as_statement()).as_deref(), Some("N = 4"));
+        assert_eq!(solve("1 + N", 5.as_statement()).as_deref(), Some("N = 4"));
+        assert_eq!(solve("10 - N", 3.as_statement()).as_deref(), Some("N = 7"));
+        assert_eq!(solve("2 * N - 1", 7.as_statement()).as_deref(), Some("N = 4"));
+        assert_eq!(solve("-N", 3.as_statement()).as_deref(), Some("N = -3"));
+        assert_eq!(solve("N * 2", 3.0.as_statement()).as_deref(), Some("N = 1.5"));
+    }
+
+    #[test]
+    fn no_solution() {
+        assert_eq!(solve("2 * N", 7.as_statement()), None); // integers must divide exactly
+        assert_eq!(solve("N + 1", Statement::Boolean(crate::statement::Boolean(true))), None);
+    }
+
+    #[test]
+    fn invertible_expressions() {
+        for source in ["N", "N + 1", "2 * (N - 3)", "-(N * 4) + 1", "N + 6 / 3"] {
+            let (statement, _) = expression(source);
+            assert_eq!(non_invertible(&statement), None, "{}", source);
+        }
+        for source in ["N * M", "N / 2", "2 / N", "N + M", "N > 1", "len(N)"] {
+            let (statement, _) = expression(source);
+            assert!(non_invertible(&statement).is_some(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn folds_constants() {
+        let (statement, symbols) = expression("N + (1 + 2) * 3");
+        assert_eq!(symbols.display(&fold(&statement)).to_string(), "N + 9");
+    }
+}
@@ -1,16 +1,31 @@
 pub mod operator_binary;
 pub mod operator_unary;
+#[allow(clippy::module_inception)]
 pub mod statement;
 pub mod operators;
 pub mod frame;
+pub mod equality;
+pub mod numbers;
+pub mod invert;
+pub mod symbol;
+pub mod error;
+pub mod builtin;
+pub mod function;
+pub mod order;
 
 mod collection;
 mod debug;
+mod display;
 
 
-pub use statement::{Statement, Float, Integer, Boolean, Atom, Variable, AsStatement};
+pub use statement::{Statement, Float, Integer, Rational, IntegerValue, FloatValue, Boolean, Atom, Text, Variable, Compound, AsStatement};
 pub use collection::{Sequence, List, UList, Object}; //, SequenceRef, ListRef, UList, ObjectRef};
 
 pub use operator_binary::{BinaryOperator};
 pub use operator_unary::{UnaryOperator};
-pub use frame::Frame;
\ No newline at end of file
+pub use frame::Frame;
+pub use equality::NumericEquality;
+pub use numbers::{Numbers, Division};
+pub use symbol::{Symbol, Symbols};
+pub use error::{EvaluationError, ErrorKind};
+pub use function::{Function, Call};
@@ -0,0 +1,33 @@
+use crate::statement::NumericEquality;
+
+/// How integers are divided, the quotient of two integers is either truncated toward zero or exact.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Division {
+    Truncate, // `7 / 2` is `3`
+    Exact,    // `7 / 2` is the fraction `7/2`, see `Rational`
+}
+
+impl Default for Division {
+    fn default() -> Self { return Division::Truncate; }
+}
+
+/// How a program computes with numbers, each program chooses its own (see `Program::set_numbers`). Integers and floats
+/// are 64-bit, arithmetic whose result they cannot represent (including a float that would be infinite) is an error.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Numbers {
+    pub equality : NumericEquality, // how numbers are compared, both when matching and by `=`
+    pub division : Division,
+}
+
+impl Numbers {
+    /// integers are divided exactly, producing a fraction unless the quotient is whole.
+    pub fn rational() -> Self {
+        return Numbers { equality : NumericEquality::default(), division : Division::Exact };
+    }
+}
+
+impl From<NumericEquality> for Numbers {
+    fn from(equality : NumericEquality) -> Self {
+        return Numbers { equality : equality, division : Division::default() };
+    }
+}
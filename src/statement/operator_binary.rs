@@ -1,10 +1,11 @@
 
 
-use crate::statement::{Statement, Float, Integer};
-use crate::statement::operators::Add;
+use std::cmp::Ordering;
+use crate::statement::{Statement, Boolean, Integer, Float, Rational, Sequence, List, UList, Object, AsStatement, EvaluationError, NumericEquality, Numbers, Division};
+use crate::statement::operators::{Add, Subtract, Multiply, Divide, Modulo, Compare};
 
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum BinaryOperator {
     Add(Box<Statement>, Box<Statement>),
     Subtract(Box<Statement>, Box<Statement>), // TODO
@@ -18,36 +19,546 @@ pub enum BinaryOperator {
     LessThan(Box<Statement>, Box<Statement>),
     LessThanEqual(Box<Statement>, Box<Statement>),
     Equal(Box<Statement>, Box<Statement>),
+    NotEqual(Box<Statement>, Box<Statement>),
+    Index(Box<Statement>, Box<Statement>), // an element of a collection `P[I]`, or a field of an object `P.key`
+    Concat(Box<Statement>, Box<Statement>),     // `L1 ++ L2`
+    In(Box<Statement>, Box<Statement>),         // `X in L`, whether `X` is an item of `L` (or a key if `L` is an object)
+    Union(Box<Statement>, Box<Statement>),      // ulists are multisets, see `BinaryOperator::union`
+    Intersect(Box<Statement>, Box<Statement>),
+    Difference(Box<Statement>, Box<Statement>), // `U1 minus U2`
 }
 
 impl BinaryOperator { 
-    pub fn evaluate(&self) -> Statement {
+
+    pub fn operands(&self) -> (&Statement, &Statement) {
+        let (x, y) = match self {
+            BinaryOperator::Add(x, y)               => (x, y),
+            BinaryOperator::Subtract(x, y)          => (x, y),
+            BinaryOperator::Multiply(x, y)          => (x, y),
+            BinaryOperator::Divide(x, y)            => (x, y),
+            BinaryOperator::Modulo(x, y)            => (x, y),
+            BinaryOperator::And(x, y)               => (x, y),
+            BinaryOperator::Or(x, y)                => (x, y),
+            BinaryOperator::GreaterThan(x, y)       => (x, y),
+            BinaryOperator::GreaterThanEqual(x, y)  => (x, y),
+            BinaryOperator::LessThan(x, y)          => (x, y),
+            BinaryOperator::LessThanEqual(x, y)     => (x, y),
+            BinaryOperator::Equal(x, y)             => (x, y),
+            BinaryOperator::NotEqual(x, y)          => (x, y),
+            BinaryOperator::Index(x, y)             => (x, y),
+            BinaryOperator::Concat(x, y)            => (x, y),
+            BinaryOperator::In(x, y)                => (x, y),
+            BinaryOperator::Union(x, y)             => (x, y),
+            BinaryOperator::Intersect(x, y)         => (x, y),
+            BinaryOperator::Difference(x, y)        => (x, y),
+        };
+        return (x.as_ref(), y.as_ref());
+    }
+
+    /// applies `f` to each operand, producing an operator of the same kind.
+    pub fn map<F : Fn(&Statement) -> Statement>(&self, f : F) -> BinaryOperator {
+        let g = |x : &Statement| Box::new(f(x));
+        return match self {
+            BinaryOperator::Add(x, y)              => BinaryOperator::Add(g(x), g(y)),
+            BinaryOperator::Subtract(x, y)         => BinaryOperator::Subtract(g(x), g(y)),
+            BinaryOperator::Multiply(x, y)         => BinaryOperator::Multiply(g(x), g(y)),
+            BinaryOperator::Divide(x, y)           => BinaryOperator::Divide(g(x), g(y)),
+            BinaryOperator::Modulo(x, y)           => BinaryOperator::Modulo(g(x), g(y)),
+            BinaryOperator::And(x, y)              => BinaryOperator::And(g(x), g(y)),
+            BinaryOperator::Or(x, y)               => BinaryOperator::Or(g(x), g(y)),
+            BinaryOperator::GreaterThan(x, y)      => BinaryOperator::GreaterThan(g(x), g(y)),
+            BinaryOperator::GreaterThanEqual(x, y) => BinaryOperator::GreaterThanEqual(g(x), g(y)),
+            BinaryOperator::LessThan(x, y)         => BinaryOperator::LessThan(g(x), g(y)),
+            BinaryOperator::LessThanEqual(x, y)    => BinaryOperator::LessThanEqual(g(x), g(y)),
+            BinaryOperator::Equal(x, y)            => BinaryOperator::Equal(g(x), g(y)),
+            BinaryOperator::NotEqual(x, y)         => BinaryOperator::NotEqual(g(x), g(y)),
+            BinaryOperator::Index(x, y)            => BinaryOperator::Index(g(x), g(y)),
+            BinaryOperator::Concat(x, y)           => BinaryOperator::Concat(g(x), g(y)),
+            BinaryOperator::In(x, y)               => BinaryOperator::In(g(x), g(y)),
+            BinaryOperator::Union(x, y)            => BinaryOperator::Union(g(x), g(y)),
+            BinaryOperator::Intersect(x, y)        => BinaryOperator::Intersect(g(x), g(y)),
+            BinaryOperator::Difference(x, y)       => BinaryOperator::Difference(g(x), g(y)),
+        };
+    }
+
+    pub fn evaluate(&self, numbers : &Numbers) -> Result<Statement, EvaluationError> {
         let y = match self {
-            BinaryOperator::Add(x,y) =>  BinaryOperator::add(&x.evaluate(), &y.evaluate()),
-            _ => unreachable!(),
+            BinaryOperator::Add(x,y)        =>  BinaryOperator::add(&x.evaluate(numbers)?, &y.evaluate(numbers)?),
+            BinaryOperator::Subtract(x,y)   =>  BinaryOperator::subtract(&x.evaluate(numbers)?, &y.evaluate(numbers)?),
+            BinaryOperator::Multiply(x,y)   =>  BinaryOperator::multiply(&x.evaluate(numbers)?, &y.evaluate(numbers)?),
+            BinaryOperator::Divide(x,y)     =>  BinaryOperator::divide(&x.evaluate(numbers)?, &y.evaluate(numbers)?, numbers.division),
+            BinaryOperator::Modulo(x,y)     =>  BinaryOperator::modulo(&x.evaluate(numbers)?, &y.evaluate(numbers)?),
+            // the right operand of `&&` and `||` is only evaluated if it decides the result
+            BinaryOperator::And(x,y)        =>  match BinaryOperator::boolean("&&", x.evaluate(numbers)?)? {
+                false => Ok(Statement::Boolean(Boolean(false))),
+                true => BinaryOperator::boolean("&&", y.evaluate(numbers)?).map(|z| Statement::Boolean(Boolean(z))),
+            },
+            BinaryOperator::Or(x,y)         =>  match BinaryOperator::boolean("||", x.evaluate(numbers)?)? {
+                true => Ok(Statement::Boolean(Boolean(true))),
+                false => BinaryOperator::boolean("||", y.evaluate(numbers)?).map(|z| Statement::Boolean(Boolean(z))),
+            },
+            BinaryOperator::GreaterThan(x, y)       => BinaryOperator::compare(&x.evaluate(numbers)?, &y.evaluate(numbers)?, |o| o.is_gt()),
+            BinaryOperator::GreaterThanEqual(x, y)  => BinaryOperator::compare(&x.evaluate(numbers)?, &y.evaluate(numbers)?, |o| o.is_ge()),
+            BinaryOperator::LessThan(x, y)          => BinaryOperator::compare(&x.evaluate(numbers)?, &y.evaluate(numbers)?, |o| o.is_lt()),
+            BinaryOperator::LessThanEqual(x, y)     => BinaryOperator::compare(&x.evaluate(numbers)?, &y.evaluate(numbers)?, |o| o.is_le()),
+            BinaryOperator::Equal(x, y)             => BinaryOperator::equal(&x.evaluate(numbers)?, &y.evaluate(numbers)?, &numbers.equality),
+            BinaryOperator::NotEqual(x, y)          => Ok(Statement::Boolean(Boolean(!same(&x.evaluate(numbers)?, &y.evaluate(numbers)?, &numbers.equality)))),
+            BinaryOperator::Index(x, y)             => BinaryOperator::index(&x.evaluate(numbers)?, &y.evaluate(numbers)?, &numbers.equality),
+            BinaryOperator::Concat(x, y)            => BinaryOperator::concat(&x.evaluate(numbers)?, &y.evaluate(numbers)?),
+            BinaryOperator::In(x, y)                => BinaryOperator::member(&x.evaluate(numbers)?, &y.evaluate(numbers)?, &numbers.equality),
+            BinaryOperator::Union(x, y)             => BinaryOperator::union(&x.evaluate(numbers)?, &y.evaluate(numbers)?, &numbers.equality),
+            BinaryOperator::Intersect(x, y)         => BinaryOperator::intersect(&x.evaluate(numbers)?, &y.evaluate(numbers)?, &numbers.equality),
+            BinaryOperator::Difference(x, y)        => BinaryOperator::difference(&x.evaluate(numbers)?, &y.evaluate(numbers)?, &numbers.equality),
         };
         return y;
     }
     
-    fn add(x : &Statement , y : &Statement ) -> Statement {
+    pub fn add(x : &Statement , y : &Statement ) -> Result<Statement, EvaluationError> {
+        if let Some((x, y)) = promote(x, y) {
+            return BinaryOperator::add(&x, &y);
+        }
         let z = match (x, y) {
-            (Statement::Float(xx),   Statement::Float(yy))   => Statement::Float(xx.add(yy)),
-            (Statement::Float(xx),   Statement::Integer(yy)) => Statement::Float(xx.add(yy)),
-            (Statement::Integer(xx), Statement::Float(yy))   => Statement::Float(xx.add(yy)),
-            (Statement::Integer(xx), Statement::Integer(yy)) => Statement::Integer(xx.add(yy)),
-            _ => unreachable!(),
+            (Statement::Float(xx),   Statement::Float(yy))   => finite(BinaryOperator::Add, x, y, xx.add(yy))?,
+            (Statement::Float(xx),   Statement::Integer(yy)) => finite(BinaryOperator::Add, x, y, xx.add(yy))?,
+            (Statement::Integer(xx), Statement::Float(yy))   => finite(BinaryOperator::Add, x, y, xx.add(yy))?,
+            (Statement::Integer(xx), Statement::Integer(yy)) => Statement::Integer(xx.add(yy).ok_or_else(|| overflow(BinaryOperator::Add, x, y))?),
+            (Statement::Rational(xx), Statement::Rational(yy)) => xx.add(yy).ok_or_else(|| overflow(BinaryOperator::Add, x, y))?.as_statement(),
+            _ => return Err(mismatch("+", x, y)),
         };
-        return z;
+        return Ok(z);
     }
 
-    
+    pub fn subtract(x : &Statement , y : &Statement ) -> Result<Statement, EvaluationError> {
+        if let Some((x, y)) = promote(x, y) {
+            return BinaryOperator::subtract(&x, &y);
+        }
+        let z = match (x, y) {
+            (Statement::Float(xx),   Statement::Float(yy))   => finite(BinaryOperator::Subtract, x, y, xx.subtract(yy))?,
+            (Statement::Float(xx),   Statement::Integer(yy)) => finite(BinaryOperator::Subtract, x, y, xx.subtract(yy))?,
+            (Statement::Integer(xx), Statement::Float(yy))   => finite(BinaryOperator::Subtract, x, y, xx.subtract(yy))?,
+            (Statement::Integer(xx), Statement::Integer(yy)) => Statement::Integer(xx.subtract(yy).ok_or_else(|| overflow(BinaryOperator::Subtract, x, y))?),
+            (Statement::Rational(xx), Statement::Rational(yy)) => xx.subtract(yy).ok_or_else(|| overflow(BinaryOperator::Subtract, x, y))?.as_statement(),
+            _ => return Err(mismatch("-", x, y)),
+        };
+        return Ok(z);
+    }
+
+    pub fn multiply(x : &Statement , y : &Statement ) -> Result<Statement, EvaluationError> {
+        if let Some((x, y)) = promote(x, y) {
+            return BinaryOperator::multiply(&x, &y);
+        }
+        let z = match (x, y) {
+            (Statement::Float(xx),   Statement::Float(yy))   => finite(BinaryOperator::Multiply, x, y, xx.multiply(yy))?,
+            (Statement::Float(xx),   Statement::Integer(yy)) => finite(BinaryOperator::Multiply, x, y, xx.multiply(yy))?,
+            (Statement::Integer(xx), Statement::Float(yy))   => finite(BinaryOperator::Multiply, x, y, xx.multiply(yy))?,
+            (Statement::Integer(xx), Statement::Integer(yy)) => Statement::Integer(xx.multiply(yy).ok_or_else(|| overflow(BinaryOperator::Multiply, x, y))?),
+            (Statement::Rational(xx), Statement::Rational(yy)) => xx.multiply(yy).ok_or_else(|| overflow(BinaryOperator::Multiply, x, y))?.as_statement(),
+            _ => return Err(mismatch("*", x, y)),
+        };
+        return Ok(z);
+    }
+
+    /// divides `x` by `y`, dividing by zero is an error (for floats as well as integers). Integer division truncates toward
+    /// zero or is exact according to `division`.
+    pub fn divide(x : &Statement , y : &Statement, division : Division) -> Result<Statement, EvaluationError> {
+        if let Some((x, y)) = promote(x, y) {
+            return BinaryOperator::divide(&x, &y, division);
+        }
+        if is_zero(y) && is_number(x) {
+            return Err(EvaluationError::DivisionByZero(expression(BinaryOperator::Divide, x, y)));
+        }
+        let z = match (x, y) {
+            (Statement::Float(xx),   Statement::Float(yy))   => finite(BinaryOperator::Divide, x, y, xx.divide(yy))?,
+            (Statement::Float(xx),   Statement::Integer(yy)) => finite(BinaryOperator::Divide, x, y, xx.divide(yy))?,
+            (Statement::Integer(xx), Statement::Float(yy))   => finite(BinaryOperator::Divide, x, y, xx.divide(yy))?,
+            (Statement::Integer(xx), Statement::Integer(yy)) => if division == Division::Exact {
+                Rational::new(xx.0, yy.0).ok_or_else(|| overflow(BinaryOperator::Divide, x, y))?.as_statement()
+            } else {
+                Statement::Integer(xx.divide(yy).ok_or_else(|| overflow(BinaryOperator::Divide, x, y))?)
+            },
+            (Statement::Rational(xx), Statement::Rational(yy)) => xx.divide(yy).ok_or_else(|| overflow(BinaryOperator::Divide, x, y))?.as_statement(),
+            _ => return Err(mismatch("/", x, y)),
+        };
+        return Ok(z);
+    }
+
+    /// the remainder of dividing `x` by `y`, it has the same sign as `x`.
+    pub fn modulo(x : &Statement , y : &Statement ) -> Result<Statement, EvaluationError> {
+        if let Some((x, y)) = promote(x, y) {
+            return BinaryOperator::modulo(&x, &y);
+        }
+        if is_zero(y) && is_number(x) {
+            return Err(EvaluationError::DivisionByZero(expression(BinaryOperator::Modulo, x, y)));
+        }
+        let z = match (x, y) {
+            (Statement::Float(xx),   Statement::Float(yy))   => finite(BinaryOperator::Modulo, x, y, xx.modulo(yy))?,
+            (Statement::Float(xx),   Statement::Integer(yy)) => finite(BinaryOperator::Modulo, x, y, xx.modulo(yy))?,
+            (Statement::Integer(xx), Statement::Float(yy))   => finite(BinaryOperator::Modulo, x, y, xx.modulo(yy))?,
+            (Statement::Integer(xx), Statement::Integer(yy)) => Statement::Integer(xx.modulo(yy).ok_or_else(|| overflow(BinaryOperator::Modulo, x, y))?),
+            (Statement::Rational(xx), Statement::Rational(yy)) => xx.modulo(yy).ok_or_else(|| overflow(BinaryOperator::Modulo, x, y))?.as_statement(),
+            _ => return Err(mismatch("%", x, y)),
+        };
+        return Ok(z);
+    }
+
+    /// compares two values, `test` decides whether their ordering satisfies the comparison. Numbers are compared by value and
+    /// lists and tuples lexicographically (e.g. `[1, 2] < [1, 3]`), other values are in the standard order (see `Ord for
+    /// Statement`, e.g. `b < c` and `5 < a`). Numbers that are unordered (i.e. NaN) never satisfy a comparison, nor do
+    /// collections whose first difference is a NaN.
+    pub fn compare<F : Fn(Ordering) -> bool>(x : &Statement, y : &Statement, test : F) -> Result<Statement, EvaluationError> {
+        return Ok(Statement::Boolean(Boolean(ordering(x, y).is_some_and(test))));
+    }
+
+    /// whether two values are equal, they are compared structurally except that numbers are compared according to `equality` 
+    /// (e.g. `[1, a] = [1.0, a]`), as they are when matching.
+    pub fn equal(x : &Statement, y : &Statement, equality : &NumericEquality) -> Result<Statement, EvaluationError> {
+        return Ok(Statement::Boolean(Boolean(same(x, y, equality))));
+    }
+
+    /// the element of the list or tuple `x` at position `y`, or the value of the key `y` in the object `x`. Index expressions in
+    /// conditions and actions are resolved by matching instead (see `Frame::resolve`), those elsewhere (e.g. in an observation)
+    /// are evaluated.
+    pub fn index(x : &Statement, y : &Statement, equality : &NumericEquality) -> Result<Statement, EvaluationError> {
+        fn position<'a>(items : &'a Sequence, x : &Statement, y : &Statement) -> Result<Option<&'a Statement>, EvaluationError> {
+            return match y {
+                Statement::Integer(Integer(i)) => Ok(usize::try_from(*i).ok().and_then(|i| items.items.get(i))),
+                _ => Err(mismatch("[]", x, y)),
+            };
+        }
+        let element = match x {
+            Statement::List(xx) if xx.spreads() == 0 => position(&xx.items, x, y)?,
+            Statement::Sequence(xx) => position(xx, x, y)?,
+            Statement::Object(xx @ Object((entries, _))) if xx.spreads() == 0 => entries.iter().find_map(|entry| match entry {
+                Statement::Sequence(entry) if same(&entry[0], y, equality) => Some(&entry[1]),
+                _ => None,
+            }),
+            _ => return Err(mismatch("[]", x, y)),
+        };
+        return element.cloned().ok_or_else(|| EvaluationError::NoElement(expression(BinaryOperator::Index, x, y)));
+    }
+
+    /// the items of `x` followed by those of `y`.
+    pub fn concat(x : &Statement, y : &Statement) -> Result<Statement, EvaluationError> {
+        return match (x, y) {
+            (Statement::List(xx), Statement::List(yy)) if xx.spreads() == 0 && yy.spreads() == 0 => {
+                let items = xx.items.iter().chain(yy.items.iter()).cloned().collect();
+                Ok(List::new(Sequence::new(items), false).as_statement())
+            },
+            _ => Err(mismatch("++", x, y)),
+        };
+    }
+
+    /// whether `x` is an item of the collection `y`, or a key of `y` if it is an object.
+    pub fn member(x : &Statement, y : &Statement, equality : &NumericEquality) -> Result<Statement, EvaluationError> {
+        let Some(items) = members(y) else {
+            return Err(mismatch("in", x, y));
+        };
+        return Ok(Statement::Boolean(Boolean(items.into_iter().any(|item| same(x, item, equality)))));
+    }
+
+    /// the union of two multisets, each item occurs as many times as it does in whichever of `x` and `y` has more of it.
+    pub fn union(x : &Statement, y : &Statement, equality : &NumericEquality) -> Result<Statement, EvaluationError> {
+        let (xx, yy) = multisets("union", x, y)?;
+        let mut items = xx.to_vec();
+        items.extend(remove(yy, xx, equality));
+        return Ok(UList::new(Sequence::new(items), false).as_statement());
+    }
+
+    /// the intersection of two multisets, each item occurs as many times as it does in whichever of `x` and `y` has less of it.
+    pub fn intersect(x : &Statement, y : &Statement, equality : &NumericEquality) -> Result<Statement, EvaluationError> {
+        let (xx, yy) = multisets("intersect", x, y)?;
+        let missing = remove(xx, yy, equality);
+        return Ok(UList::new(Sequence::new(remove(xx, &missing, equality)), false).as_statement());
+    }
+
+    /// the difference of two multisets, each occurrence of an item in `y` removes one occurrence of it from `x`.
+    pub fn difference(x : &Statement, y : &Statement, equality : &NumericEquality) -> Result<Statement, EvaluationError> {
+        let (xx, yy) = multisets("minus", x, y)?;
+        return Ok(UList::new(Sequence::new(remove(xx, yy, equality)), false).as_statement());
+    }
+
+    fn boolean(operator : &'static str, x : Statement) -> Result<bool, EvaluationError> {
+        return match x {
+            Statement::Boolean(Boolean(z)) => Ok(z),
+            x => Err(EvaluationError::TypeMismatch { operator : operator, operands : vec![x] }),
+        };
+    }
 }
 
+impl From<BinaryOperator> for Statement {
+    fn from(item : BinaryOperator) -> Self {
+        return Statement::BinaryOperator(item);
+    }
+}
+
+// rationals are combined with an integer as rationals and with a float as floats.
+fn promote(x : &Statement, y : &Statement) -> Option<(Statement, Statement)> {
+    return match (x, y) {
+        (Statement::Rational(_), Statement::Integer(yy)) => Some((x.clone(), Statement::Rational(Rational::from(*yy)))),
+        (Statement::Integer(xx), Statement::Rational(_)) => Some((Statement::Rational(Rational::from(*xx)), y.clone())),
+        (Statement::Rational(xx), Statement::Float(_))   => Some((xx.to_float().as_statement(), y.clone())),
+        (Statement::Float(_), Statement::Rational(yy))   => Some((x.clone(), yy.to_float().as_statement())),
+        _ => None,
+    };
+}
 
-fn main() {
+fn expression<F : Fn(Box<Statement>, Box<Statement>) -> BinaryOperator>(operator : F, x : &Statement, y : &Statement) -> Statement {
+    return Statement::BinaryOperator(operator(Box::new(x.clone()), Box::new(y.clone())));
+}
 
-    //let x1 = Statement::BinaryOperator(BinaryOperator::Add(&1.0.as_statement(), &1.as_statement()));
-    //let x2 = BinaryOperator::Add(&1.0.as_statement(), &x1);
-    //let s = Statement::BinaryOperator(x2);
-    //println!("{:?}", s.evaluate());
+fn overflow<F : Fn(Box<Statement>, Box<Statement>) -> BinaryOperator>(operator : F, x : &Statement, y : &Statement) -> EvaluationError {
+    return EvaluationError::Overflow(expression(operator, x, y));
+}
+
+// a float result that is not finite (e.g. `1e308 * 10.0`) overflows, as an integer result that cannot be represented does.
+fn finite<F : Fn(Box<Statement>, Box<Statement>) -> BinaryOperator>(operator : F, x : &Statement, y : &Statement, z : Float) -> Result<Statement, EvaluationError> {
+    if z.0.is_finite() {
+        return Ok(Statement::Float(z));
+    }
+    return Err(overflow(operator, x, y));
+}
+
+fn mismatch(operator : &'static str, x : &Statement, y : &Statement) -> EvaluationError {
+    return EvaluationError::TypeMismatch { operator : operator, operands : vec![x.clone(), y.clone()] };
+}
+
+/// the items of a collection (that has no spread captures), or the keys of an object.
+pub fn members(x : &Statement) -> Option<Vec<&Statement>> {
+    return match x {
+        Statement::List(xx) if xx.spreads() == 0 => Some(xx.items.iter().collect()),
+        Statement::UList(xx) if xx.spreads() == 0 => Some(xx.items.iter().collect()),
+        Statement::Sequence(xx) => Some(xx.iter().collect()),
+        Statement::Object(xx @ Object((entries, _))) if xx.spreads() == 0 => entries.iter().map(|entry| match entry {
+            Statement::Sequence(entry) => entry.items.first(),
+            _ => None,
+        }).collect(),
+        _ => None,
+    };
+}
+
+fn multisets<'a>(operator : &'static str, x : &'a Statement, y : &'a Statement) -> Result<(&'a [Statement], &'a [Statement]), EvaluationError> {
+    return match (x, y) {
+        (Statement::UList(xx), Statement::UList(yy)) if xx.spreads() == 0 && yy.spreads() == 0 => Ok((&xx.items.items, &yy.items.items)),
+        _ => Err(mismatch(operator, x, y)),
+    };
+}
+
+// the items of `x` that remain once each item of `y` has removed one occurrence of itself.
+fn remove(x : &[Statement], y : &[Statement], equality : &NumericEquality) -> Vec<Statement> {
+    let mut removed = vec![false; x.len()];
+    for item in y {
+        if let Some(i) = (0..x.len()).find(|i| !removed[*i] && same(&x[*i], item, equality)) {
+            removed[i] = true;
+        }
+    }
+    return x.iter().zip(removed).filter(|(_, removed)| !removed).map(|(item, _)| item.clone()).collect();
+}
+
+// how two values are ordered, `None` if they are unordered (e.g. NaN).
+fn ordering(x : &Statement, y : &Statement) -> Option<Ordering> {
+    if let Some((x, y)) = promote(x, y) {
+        return ordering(&x, &y);
+    }
+    return match (x, y) {
+        (Statement::Float(xx),   Statement::Float(yy))   => xx.compare(yy),
+        (Statement::Float(xx),   Statement::Integer(yy)) => xx.compare(yy),
+        (Statement::Integer(xx), Statement::Float(yy))   => xx.compare(yy),
+        (Statement::Integer(xx), Statement::Integer(yy)) => xx.compare(yy),
+        (Statement::Rational(xx), Statement::Rational(yy)) => xx.compare(yy),
+        (Statement::List(xx),    Statement::List(yy)) if xx.spreads() == 0 && yy.spreads() == 0 => lexicographic(&xx.items, &yy.items),
+        (Statement::Sequence(xx), Statement::Sequence(yy)) => lexicographic(xx, yy),
+        _ => Some(x.cmp(y)),
+    };
+}
+
+// orders two sequences by their first difference, or by their length if one is a prefix of the other.
+fn lexicographic(x : &Sequence, y : &Sequence) -> Option<Ordering> {
+    for (xx, yy) in x.iter().zip(y.iter()) {
+        match ordering(xx, yy) {
+            Some(Ordering::Equal) => continue,
+            other => return other,
+        }
+    }
+    return Some(x.len().cmp(&y.len()));
+}
+
+// whether two values are equal, as `=` compares them (numbers according to `equality`).
+fn same(x : &Statement, y : &Statement, equality : &NumericEquality) -> bool {
+    if let Some(result) = equality.numeric(x, y) {
+        return result;
+    }
+    // ulists and objects are multisets of items (or entries), each item must be the same as a different item of the other
+    let unordered = match (x, y) {
+        (Statement::UList(xx), Statement::UList(yy)) if !xx.ispiped && !yy.ispiped => Some((&xx.items, &yy.items)),
+        (Statement::Object(Object((xx, false))), Statement::Object(Object((yy, false)))) => Some((xx, yy)),
+        _ => None,
+    };
+    if let Some((xx, yy)) = unordered {
+        return xx.len() == yy.len() && remove(&xx.items, &yy.items, equality).is_empty();
+    }
+    let (xx, yy) = (x.children(), y.children());
+    if xx.is_empty() && yy.is_empty() {
+        return x == y;
+    }
+    // statements with the same shape (e.g. the same name and number of arguments) are equal if their children are equal
+    let shape = |z : &Statement| z.map(|_| Statement::Empty);
+    return shape(x) == shape(y) && xx.into_iter().zip(yy).all(|(xx, yy)| same(xx, yy, equality));
+}
+
+fn is_number(x : &Statement) -> bool {
+    return matches!(x, Statement::Integer(_) | Statement::Float(_) | Statement::Rational(_));
+}
+
+fn is_zero(x : &Statement) -> bool {
+    return matches!(x, Statement::Integer(Integer(0)) | Statement::Rational(Rational { numerator : 0, .. })) || matches!(x, Statement::Float(Float(y)) if *y == 0.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::statement::UnaryOperator;
+    use crate::interpret::tests::{expression, expressions};
+
+    // the value of the expression written in `source`, as it is displayed.
+    fn evaluate(source : &str) -> Result<String, EvaluationError> {
+        return evaluate_with(source, Numbers::default());
+    }
+
+    // the value of the expression written in `source` computed according to `numbers`, as it is displayed.
+    fn evaluate_with(source : &str, numbers : Numbers) -> Result<String, EvaluationError> {
+        let (statement, symbols) = expression(source);
+        return statement.evaluate(&numbers).map(|x| symbols.display(&x).to_string());
+    }
+
+    #[test]
+    fn integer_division() {
+        assert_eq!(evaluate("7 / 2").unwrap(), "3");
+        assert_eq!(evaluate("-7 / 2").unwrap(), "-3");
+        assert_eq!(evaluate_with("7 / 2", Numbers::rational()).unwrap(), "7/2");
+        assert_eq!(evaluate_with("-6 / 4", Numbers::rational()).unwrap(), "-3/2");
+        assert_eq!(evaluate_with("6 / 3", Numbers::rational()).unwrap(), "2");
+        // fractions combine exactly with integers and become floats with floats
+        assert_eq!(evaluate_with("1 / 3 + 1 / 6", Numbers::rational()).unwrap(), "1/2");
+        assert_eq!(evaluate_with("1 / 2 * 4", Numbers::rational()).unwrap(), "2");
+        assert_eq!(evaluate_with("1 / 4 + 0.5", Numbers::rational()).unwrap(), "0.75");
+        assert_eq!(evaluate_with("1 / 3 < 1 / 2", Numbers::rational()).unwrap(), "true");
+        assert!(matches!(evaluate_with("1 / 0", Numbers::rational()), Err(EvaluationError::DivisionByZero(_))));
+    }
+
+    #[test]
+    fn overflow() {
+        for source in ["9223372036854775807 + 1", "-9223372036854775808 - 1", "4294967296 * 4294967296", "-9223372036854775808 / -1", "-(-9223372036854775808)"] {
+            assert!(matches!(evaluate(source), Err(EvaluationError::Overflow(_))), "{}", source);
+        }
+        // floats that would be infinite overflow as well
+        for source in ["1e308 * 10.0", "-1e308 - 1e308", "1e308 / 1e-308", "1e300 * 1e300"] {
+            assert!(matches!(evaluate(source), Err(EvaluationError::Overflow(_))), "{}", source);
+        }
+        assert!(matches!(evaluate_with("9223372036854775807 / 2 * 3", Numbers::rational()), Err(EvaluationError::Overflow(_))));
+        assert_eq!(evaluate("1e308 * 1.5").unwrap(), "1.5e308");
+    }
+
+    #[test]
+    fn equality_policy() {
+        let (statements, symbols) = expressions(&["[1.0001, a] = [1, a]", "1.0001 in [2, 1]", "{1.0001} minus {1}"]);
+        let (exact, tolerant) = (NumericEquality::exact(), NumericEquality::tolerant(1e-3, 0.0));
+        let display = |x : &Statement, equality : &NumericEquality| symbols.display(&x.evaluate(&(*equality).into()).unwrap()).to_string();
+        assert_eq!(display(&statements[0], &exact), "false");
+        assert_eq!(display(&statements[0], &tolerant), "true");
+        assert_eq!(display(&statements[1], &tolerant), "true");
+        assert_eq!(display(&statements[2], &exact), "{1.0001}");
+        assert_eq!(display(&statements[2], &tolerant), "{}");
+    }
+
+    #[test]
+    fn not_equal_is_the_negation_of_equal() {
+        let pairs = [("{1, 2}", "{2, 1}"), ("{1, 1, 2}", "{1, 2, 2}"), ("{a : 1, b : [2]}", "{b : [2], a : 1}"), ("{a : 1}", "{a : 2}"),
+            ("[1, 2]", "[2, 1]"), ("{1.0001, 2}", "{2, 1}"), ("1.0001", "1"), ("(a, {1, 2})", "(a, {2, 1.0})")];
+        for equality in [NumericEquality::exact(), NumericEquality::strict(), NumericEquality::tolerant(1e-3, 0.0)] {
+            for (x, y) in pairs {
+                let (statements, _) = expressions(&[&format!("{} = {}", x, y), &format!("{} != {}", x, y)]);
+                let (equal, not_equal) = (statements[0].evaluate(&equality.into()).unwrap(), statements[1].evaluate(&equality.into()).unwrap());
+                assert_eq!(equal, UnaryOperator::not(&not_equal).unwrap(), "{} = {} under {:?}", x, y, equality);
+            }
+        }
+        let (statements, symbols) = expressions(&["{1, 2} = {2, 1}", "{1, 1, 2} = {1, 2, 2}", "{a : 1, b : 2} = {b : 2, a : 1}"]);
+        let values : Vec<String> = statements.iter().map(|x| symbols.display(&x.evaluate(&Numbers::default()).unwrap()).to_string()).collect();
+        assert_eq!(values, ["true", "false", "true"]);
+    }
+
+    #[test]
+    fn index() {
+        assert_eq!(evaluate("[1, 2, 3][1]").unwrap(), "2");
+        assert_eq!(evaluate("(1, a)[1]").unwrap(), "a");
+        assert_eq!(evaluate("{a : 1, b : [2]}.b[0]").unwrap(), "2");
+        assert_eq!(evaluate("[1, 2][0 + 1] * 2").unwrap(), "4");
+        assert!(matches!(evaluate("[1, 2][2]"), Err(EvaluationError::NoElement(_))));
+        assert!(matches!(evaluate("[1, 2][-1]"), Err(EvaluationError::NoElement(_))));
+        assert!(matches!(evaluate("{a : 1}.b"), Err(EvaluationError::NoElement(_))));
+        assert!(matches!(evaluate("(3)[0]"), Err(EvaluationError::TypeMismatch { .. })));
+        assert!(matches!(evaluate("[1][a]"), Err(EvaluationError::TypeMismatch { .. })));
+    }
+
+    #[test]
+    fn comparisons() {
+        let cases = [("1 < 2.5", "true"), ("1.0 < 1", "false"), ("1.0 >= 1", "true"), ("\"ab\" < \"b\"", "true"), ("false < true", "true"),
+            ("[1, 2] < [1, 3]", "true"), ("[1, 2] < [1]", "false"), ("(1, b) > (1, a)", "true"), ("[1.0, b] < [1, c]", "true"),
+            // atoms by name, whenever they were first seen
+            ("zulu_041 < alpha_041", "false"), ("alpha_041 < zulu_041", "true"), ("alpha_041 <= alpha_041", "true"),
+            // values of different kinds in the standard order
+            ("5 < a", "true"), ("a < 5", "false"), ("true < 0", "true"), ("a < \"a\"", "true"), ("\"z\" < [1]", "true"),
+            ("[1] < {1}", "true"), ("{a : 1} > {1}", "true"), ("1 < [a]", "true")];
+        for (source, value) in cases {
+            assert_eq!(evaluate(source).unwrap(), value, "{}", source);
+        }
+    }
+
+    #[test]
+    fn member() {
+        let cases = [("2 in [1, 2, 2]", "true"), ("3 in [1, 2]", "false"), ("2 in {2, 1}", "true"), ("a in (b, a)", "true"),
+            ("a in {a : 1}", "true"), ("1 in {a : 1}", "false"), ("[1] in [[1], 2]", "true"), ("{2, 1} in [{1, 2}]", "true"),
+            ("[2, 1] in [[1, 2]]", "false"), ("1 in []", "false"), ("1 in {}", "false")];
+        for (source, value) in cases {
+            assert_eq!(evaluate(source).unwrap(), value, "{}", source);
+        }
+        for source in ["1 in 1", "a in a", "1 in \"1\""] {
+            assert!(matches!(evaluate(source), Err(EvaluationError::TypeMismatch { operator : "in", .. })), "{}", source);
+        }
+    }
+
+    #[test]
+    fn concat() {
+        assert_eq!(evaluate("[1, 2] ++ [2, 1]").unwrap(), "[1, 2, 2, 1]");
+        assert_eq!(evaluate("[] ++ [a]").unwrap(), "[a]");
+        assert_eq!(evaluate("[a] ++ []").unwrap(), "[a]");
+        for source in ["{1} ++ {2}", "[1] ++ {2}", "[1] ++ 2", "(1, 2) ++ [3]", "\"a\" ++ \"b\""] {
+            assert!(matches!(evaluate(source), Err(EvaluationError::TypeMismatch { operator : "++", .. })), "{}", source);
+        }
+    }
+
+    #[test]
+    fn multisets() {
+        let cases = [
+            ("{1, 2} union {2, 3}", "{1, 2, 3}"),
+            ("{1, 1, 2} union {1, 3}", "{1, 1, 2, 3}"),
+            ("{1} union {1, 1}", "{1, 1}"),
+            ("{} union {a}", "{a}"),
+            ("{1, 2, 2} intersect {2, 2, 3}", "{2, 2}"),
+            ("{1, 2, 2} intersect {2}", "{2}"),
+            ("{[1, 2], [2, 1]} intersect {[1, 2]}", "{[1, 2]}"),
+            ("{{1, 2}} intersect {{2, 1}}", "{{1, 2}}"),
+            ("{1, 2} intersect {3}", "{}"),
+            ("{1, 2, 2, 3} minus {2, 4}", "{1, 2, 3}"),
+            ("{1, 2, 2} minus {2, 2, 2}", "{1}"),
+            ("{1, 2} minus {}", "{1, 2}"),
+        ];
+        for (source, value) in cases {
+            assert_eq!(evaluate(source).unwrap(), value, "{}", source);
+        }
+        // only unordered lists are multisets
+        for (operator, source) in [("union", "[1, 2] union [3]"), ("union", "{1} union [1]"), ("intersect", "{1} intersect 1"),
+            ("intersect", "(1, 2) intersect {1}"), ("minus", "{a : 1} minus {a}"), ("minus", "[1] minus {1}")] {
+            assert!(matches!(evaluate(source), Err(EvaluationError::TypeMismatch { operator : x, .. }) if x == operator), "{}", source);
+        }
+    }
 }
@@ -1,32 +1,58 @@
 
-use crate::statement::{Statement, Float, Integer};
+use crate::statement::{Statement, EvaluationError, Numbers};
 use crate::statement::operators::Negate;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum UnaryOperator {
     Minus(Box<Statement>),
-    Not(Box<Statement>), // TODO
+    Not(Box<Statement>),
 }
 
 
 impl UnaryOperator { 
-    pub fn evaluate(&self) -> Statement {
+
+    pub fn operand(&self) -> &Statement {
+        return match self {
+            UnaryOperator::Minus(x) => x,
+            UnaryOperator::Not(x)   => x,
+        };
+    }
+
+    /// applies `f` to the operand, producing an operator of the same kind.
+    pub fn map<F : Fn(&Statement) -> Statement>(&self, f : F) -> UnaryOperator {
+        return match self {
+            UnaryOperator::Minus(x) => UnaryOperator::Minus(Box::new(f(x))),
+            UnaryOperator::Not(x)   => UnaryOperator::Not(Box::new(f(x))),
+        };
+    }
+
+    pub fn evaluate(&self, numbers : &Numbers) -> Result<Statement, EvaluationError> {
         let y = match self {
-            UnaryOperator::Minus(x) =>  UnaryOperator::negate(&x.evaluate()),
-            _ => unreachable!(),
+            UnaryOperator::Minus(x) =>  UnaryOperator::negate(&x.evaluate(numbers)?),
+            UnaryOperator::Not(x)   =>  UnaryOperator::not(&x.evaluate(numbers)?),
         };
         return y;
     }
     
-    fn negate(x : &Statement) -> Statement {
+    pub fn negate(x : &Statement) -> Result<Statement, EvaluationError> {
         let z = match x {
-            Statement::Boolean(xx)    => Statement::Boolean(xx.negate()),
             Statement::Float(xx)   => Statement::Float(xx.negate()),
-            Statement::Integer(xx) => Statement::Integer(xx.negate()),
-            _ => unreachable!(),
+            Statement::Integer(xx) => Statement::Integer(xx.negate().ok_or_else(|| overflow(x))?),
+            Statement::Rational(xx) => Statement::Rational(xx.negate().ok_or_else(|| overflow(x))?),
+            _ => return Err(EvaluationError::TypeMismatch { operator : "-", operands : vec![x.clone()] }),
         };
-        return z;
+        return Ok(z);
     }
 
-    
+    pub fn not(x : &Statement) -> Result<Statement, EvaluationError> {
+        let z = match x {
+            Statement::Boolean(xx) => Statement::Boolean(xx.negate()),
+            _ => return Err(EvaluationError::TypeMismatch { operator : "!", operands : vec![x.clone()] }),
+        };
+        return Ok(z);
+    }
+}
+
+fn overflow(x : &Statement) -> EvaluationError {
+    return EvaluationError::Overflow(Statement::UnaryOperator(UnaryOperator::Minus(Box::new(x.clone()))));
 }
@@ -1,26 +1,32 @@
-use crate::statement::{Integer, Float};
+use crate::statement::{Integer, Float, Rational, FloatValue};
 
 pub trait Add<Rhs = Self> {
     type Output;
     fn add(&self, rhs: &Rhs) -> Self::Output;
 }
 
+// integer arithmetic is checked, `None` if the result overflows (or is undefined)
 impl Add<Integer> for Integer {
-    type Output = Integer;
-    fn add(&self, rhs: &Integer) -> Integer { return Integer(self.0 + rhs.0); }
+    type Output = Option<Integer>;
+    fn add(&self, rhs: &Integer) -> Option<Integer> { return self.0.checked_add(rhs.0).map(Integer); }
 }
 
 impl Add<Float> for Integer {
     type Output = Float;
-    fn add(&self, rhs: &Float) -> Float { return Float((self.0 as f32) + rhs.0); }
+    fn add(&self, rhs: &Float) -> Float { return Float((self.0 as FloatValue) + rhs.0); }
 }
 
 impl Add<Integer> for Float {
     type Output = Float;
-    fn add(&self, rhs: &Integer) -> Float { return Float(self.0 + (rhs.0 as f32)); }
+    fn add(&self, rhs: &Integer) -> Float { return Float(self.0 + (rhs.0 as FloatValue)); }
 }
 
 impl Add<Float> for Float {
     type Output = Float;
     fn add(&self, rhs: &Float) -> Float { return Float(self.0 + rhs.0); }
-}
\ No newline at end of file
+}
+
+impl Add<Rational> for Rational {
+    type Output = Option<Rational>;
+    fn add(&self, rhs: &Rational) -> Option<Rational> { return Rational::new(self.numerator.checked_mul(rhs.denominator)?.checked_add(rhs.numerator.checked_mul(self.denominator)?)?, self.denominator.checked_mul(rhs.denominator)?); }
+}
@@ -0,0 +1,73 @@
+use std::cmp::Ordering;
+use crate::statement::{Integer, Float, Rational, Text};
+
+pub trait Compare<Rhs = Self> {
+    // numbers that are unordered (i.e. NaN) have no ordering
+    fn compare(&self, rhs: &Rhs) -> Option<Ordering>;
+}
+
+impl Compare<Integer> for Integer {
+    fn compare(&self, rhs: &Integer) -> Option<Ordering> { return Some(self.0.cmp(&rhs.0)); }
+}
+
+impl Compare<Float> for Integer {
+    fn compare(&self, rhs: &Float) -> Option<Ordering> { return exact(*self, *rhs).map(Ordering::reverse); }
+}
+
+impl Compare<Integer> for Float {
+    fn compare(&self, rhs: &Integer) -> Option<Ordering> { return exact(*rhs, *self); }
+}
+
+impl Compare<Float> for Float {
+    fn compare(&self, rhs: &Float) -> Option<Ordering> { return self.0.partial_cmp(&rhs.0); }
+}
+
+impl Compare<Rational> for Rational {
+    // denominators are positive, so the fractions compare as their cross products (which cannot overflow an i128)
+    fn compare(&self, rhs: &Rational) -> Option<Ordering> {
+        return Some((self.numerator as i128 * rhs.denominator as i128).cmp(&(rhs.numerator as i128 * self.denominator as i128)));
+    }
+}
+
+impl Compare<Text> for Text {
+    // strings are ordered by their characters (code points)
+    fn compare(&self, rhs: &Text) -> Option<Ordering> { return Some(self.0.cmp(&rhs.0)); }
+}
+
+// orders a float relative to an integer without rounding the integer, `None` if the float is NaN.
+fn exact(integer : Integer, float : Float) -> Option<Ordering> {
+    let (x, y) = (integer.0, float.0);
+    if y.is_nan() {
+        return None;
+    }
+    // floats at or beyond 2^63 are larger than every integer, and those below -2^63 are smaller
+    if y >= 9_223_372_036_854_775_808.0 {
+        return Some(Ordering::Greater);
+    }
+    if y < -9_223_372_036_854_775_808.0 {
+        return Some(Ordering::Less);
+    }
+    let whole = y.trunc();
+    return Some((whole as i64).cmp(&x).then_with(|| (y - whole).partial_cmp(&0.0).unwrap()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::statement::{NumericEquality, AsStatement};
+
+    #[test]
+    fn integers_and_floats_are_not_rounded() {
+        let (integer, float) = (Integer(9_007_199_254_740_993), Float(9_007_199_254_740_992.0)); // 2^53 + 1 and 2^53
+        assert_eq!(integer.compare(&float), Some(Ordering::Greater));
+        assert_eq!(float.compare(&integer), Some(Ordering::Less));
+        assert_eq!(Integer(i64::MAX).compare(&Float(9_223_372_036_854_775_808.0)), Some(Ordering::Less));
+        assert_eq!(Integer(-1).compare(&Float(-0.5)), Some(Ordering::Less));
+        assert_eq!(Integer(1).compare(&Float(f64::NAN)), None);
+        // equality agrees with the order unless there is a tolerance
+        let (x, y) = (integer.as_statement(), float.as_statement());
+        assert_eq!(NumericEquality::exact().numeric(&x, &y), Some(false));
+        assert_eq!(NumericEquality::tolerant(1.0, 0.0).numeric(&x, &y), Some(true));
+        assert_eq!(NumericEquality::exact().numeric(&Integer(3).as_statement(), &Float(3.0).as_statement()), Some(true));
+    }
+}
@@ -0,0 +1,32 @@
+use crate::statement::{Integer, Float, Rational, FloatValue};
+
+pub trait Divide<Rhs = Self> {
+    type Output;
+    fn divide(&self, rhs: &Rhs) -> Self::Output;
+}
+
+// integer arithmetic is checked, `None` if the result overflows (or is undefined)
+impl Divide<Integer> for Integer {
+    type Output = Option<Integer>;
+    fn divide(&self, rhs: &Integer) -> Option<Integer> { return self.0.checked_div(rhs.0).map(Integer); }
+}
+
+impl Divide<Float> for Integer {
+    type Output = Float;
+    fn divide(&self, rhs: &Float) -> Float { return Float((self.0 as FloatValue) / rhs.0); }
+}
+
+impl Divide<Integer> for Float {
+    type Output = Float;
+    fn divide(&self, rhs: &Integer) -> Float { return Float(self.0 / (rhs.0 as FloatValue)); }
+}
+
+impl Divide<Float> for Float {
+    type Output = Float;
+    fn divide(&self, rhs: &Float) -> Float { return Float(self.0 / rhs.0); }
+}
+
+impl Divide<Rational> for Rational {
+    type Output = Option<Rational>;
+    fn divide(&self, rhs: &Rational) -> Option<Rational> { return Rational::new(self.numerator.checked_mul(rhs.denominator)?, self.denominator.checked_mul(rhs.numerator)?); }
+}
@@ -1,6 +1,16 @@
 pub mod add;
+pub mod subtract;
+pub mod multiply;
+pub mod divide;
+pub mod modulo;
+pub mod compare;
 pub mod negate;
 
 
 pub use add::Add;
-pub use negate::Negate;
\ No newline at end of file
+pub use subtract::Subtract;
+pub use multiply::Multiply;
+pub use divide::Divide;
+pub use modulo::Modulo;
+pub use compare::Compare;
+pub use negate::Negate;
@@ -0,0 +1,32 @@
+use crate::statement::{Integer, Float, Rational, FloatValue};
+
+pub trait Modulo<Rhs = Self> {
+    type Output;
+    fn modulo(&self, rhs: &Rhs) -> Self::Output;
+}
+
+// integer arithmetic is checked, `None` if the result overflows (or is undefined)
+impl Modulo<Integer> for Integer {
+    type Output = Option<Integer>;
+    fn modulo(&self, rhs: &Integer) -> Option<Integer> { return self.0.checked_rem(rhs.0).map(Integer); }
+}
+
+impl Modulo<Float> for Integer {
+    type Output = Float;
+    fn modulo(&self, rhs: &Float) -> Float { return Float((self.0 as FloatValue) % rhs.0); }
+}
+
+impl Modulo<Integer> for Float {
+    type Output = Float;
+    fn modulo(&self, rhs: &Integer) -> Float { return Float(self.0 % (rhs.0 as FloatValue)); }
+}
+
+impl Modulo<Float> for Float {
+    type Output = Float;
+    fn modulo(&self, rhs: &Float) -> Float { return Float(self.0 % rhs.0); }
+}
+
+impl Modulo<Rational> for Rational {
+    type Output = Option<Rational>;
+    fn modulo(&self, rhs: &Rational) -> Option<Rational> { return Rational::new(self.numerator.checked_mul(rhs.denominator)?.checked_rem(rhs.numerator.checked_mul(self.denominator)?)?, self.denominator.checked_mul(rhs.denominator)?); }
+}
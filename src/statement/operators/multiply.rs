@@ -0,0 +1,32 @@
+use crate::statement::{Integer, Float, Rational, FloatValue};
+
+pub trait Multiply<Rhs = Self> {
+    type Output;
+    fn multiply(&self, rhs: &Rhs) -> Self::Output;
+}
+
+// integer arithmetic is checked, `None` if the result overflows (or is undefined)
+impl Multiply<Integer> for Integer {
+    type Output = Option<Integer>;
+    fn multiply(&self, rhs: &Integer) -> Option<Integer> { return self.0.checked_mul(rhs.0).map(Integer); }
+}
+
+impl Multiply<Float> for Integer {
+    type Output = Float;
+    fn multiply(&self, rhs: &Float) -> Float { return Float((self.0 as FloatValue) * rhs.0); }
+}
+
+impl Multiply<Integer> for Float {
+    type Output = Float;
+    fn multiply(&self, rhs: &Integer) -> Float { return Float(self.0 * (rhs.0 as FloatValue)); }
+}
+
+impl Multiply<Float> for Float {
+    type Output = Float;
+    fn multiply(&self, rhs: &Float) -> Float { return Float(self.0 * rhs.0); }
+}
+
+impl Multiply<Rational> for Rational {
+    type Output = Option<Rational>;
+    fn multiply(&self, rhs: &Rational) -> Option<Rational> { return Rational::new(self.numerator.checked_mul(rhs.numerator)?, self.denominator.checked_mul(rhs.denominator)?); }
+}
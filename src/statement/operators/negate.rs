@@ -1,18 +1,27 @@
-
-use crate::statement::{Float, Integer, Boolean};
+use crate::statement::{Float, Integer, Boolean, Rational};
 
 pub trait Negate {
-    fn negate(self) -> Self;
+    type Output;
+    fn negate(self) -> Self::Output;
 }
 
 impl Negate for Float {
-    fn negate(self) -> Self { return Float(-self.0); }
+    type Output = Float;
+    fn negate(self) -> Float { return Float(-self.0); }
 }
 
+// `None` if the result overflows
 impl Negate for Integer {
-    fn negate(self) -> Self { return Integer(-self.0); }
+    type Output = Option<Integer>;
+    fn negate(self) -> Option<Integer> { return self.0.checked_neg().map(Integer); }
+}
+
+impl Negate for Rational {
+    type Output = Option<Rational>;
+    fn negate(self) -> Option<Rational> { return Rational::new(self.numerator.checked_neg()?, self.denominator); }
 }
 
 impl Negate for Boolean {
-    fn negate(self) -> Self { return Boolean(!self.0); }
+    type Output = Boolean;
+    fn negate(self) -> Boolean { return Boolean(!self.0); }
 }
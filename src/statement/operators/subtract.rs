@@ -0,0 +1,32 @@
+use crate::statement::{Integer, Float, Rational, FloatValue};
+
+pub trait Subtract<Rhs = Self> {
+    type Output;
+    fn subtract(&self, rhs: &Rhs) -> Self::Output;
+}
+
+// integer arithmetic is checked, `None` if the result overflows (or is undefined)
+impl Subtract<Integer> for Integer {
+    type Output = Option<Integer>;
+    fn subtract(&self, rhs: &Integer) -> Option<Integer> { return self.0.checked_sub(rhs.0).map(Integer); }
+}
+
+impl Subtract<Float> for Integer {
+    type Output = Float;
+    fn subtract(&self, rhs: &Float) -> Float { return Float((self.0 as FloatValue) - rhs.0); }
+}
+
+impl Subtract<Integer> for Float {
+    type Output = Float;
+    fn subtract(&self, rhs: &Integer) -> Float { return Float(self.0 - (rhs.0 as FloatValue)); }
+}
+
+impl Subtract<Float> for Float {
+    type Output = Float;
+    fn subtract(&self, rhs: &Float) -> Float { return Float(self.0 - rhs.0); }
+}
+
+impl Subtract<Rational> for Rational {
+    type Output = Option<Rational>;
+    fn subtract(&self, rhs: &Rational) -> Option<Rational> { return Rational::new(self.numerator.checked_mul(rhs.denominator)?.checked_sub(rhs.numerator.checked_mul(self.denominator)?)?, self.denominator.checked_mul(rhs.denominator)?); }
+}
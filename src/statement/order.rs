@@ -0,0 +1,188 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use crate::statement::{Statement, Float, Rational, Sequence, UList, Object, FloatValue, Symbol};
+use crate::statement::operators::Compare;
+
+// The standard order of statements, a total order that is used to sort statements and to key maps by them. Statements of
+// different kinds are ordered by kind:
+//
+//      variables < booleans < numbers < atoms < strings < tuples < lists < ulists < objects < compounds < calls < operators
+//
+// Numbers are ordered by value whatever their type, a float comes before an integer or rational with the same value (so
+// `1.0 < 1`). Every NaN is equal to every other NaN and comes after every other number, `-0.0` is equal to `0.0`. Atoms are
+// ordered by their names, strings by their characters and collections (and the arguments of compounds) lexicographically
+// by their items. Ulists and objects are unordered, so their items (or entries) are
+// sorted before they are compared, e.g. `{2, 1} = {1, 2}`. Equality is structural, so it agrees with the standard order.
+
+impl PartialEq for Float {
+    fn eq(&self, other : &Self) -> bool {
+        return self.0 == other.0 || (self.0.is_nan() && other.0.is_nan());
+    }
+}
+
+impl Eq for Float {}
+
+impl Hash for Float {
+    // equal floats must hash equally, so zeros and NaNs are each hashed as one value
+    fn hash<H : Hasher>(&self, state : &mut H) {
+        let value = if self.0.is_nan() { FloatValue::NAN } else if self.0 == 0.0 { 0.0 } else { self.0 };
+        value.to_bits().hash(state);
+    }
+}
+
+impl PartialEq for UList {
+    fn eq(&self, other : &Self) -> bool {
+        return self.ispiped == other.ispiped && sorted(&self.items, self.ispiped) == sorted(&other.items, other.ispiped);
+    }
+}
+
+impl Eq for UList {}
+
+impl Hash for UList {
+    // equal ulists must hash equally, so their items are hashed in the standard order
+    fn hash<H : Hasher>(&self, state : &mut H) {
+        sorted(&self.items, self.ispiped).hash(state);
+        self.ispiped.hash(state);
+    }
+}
+
+impl PartialEq for Object {
+    fn eq(&self, other : &Self) -> bool {
+        let (Object((x, xp)), Object((y, yp))) = (self, other);
+        return xp == yp && sorted(x, *xp) == sorted(y, *yp);
+    }
+}
+
+impl Eq for Object {}
+
+impl Hash for Object {
+    fn hash<H : Hasher>(&self, state : &mut H) {
+        let Object((entries, ispiped)) = self;
+        sorted(entries, *ispiped).hash(state);
+        ispiped.hash(state);
+    }
+}
+
+impl PartialOrd for Statement {
+    fn partial_cmp(&self, other : &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl Ord for Statement {
+    fn cmp(&self, other : &Self) -> Ordering {
+        return rank(self).cmp(&rank(other)).then_with(|| match (self, other) {
+            (Statement::Variable(x), Statement::Variable(y))        => (x.slot, x.name).cmp(&(y.slot, y.name)),
+            (Statement::Boolean(x), Statement::Boolean(y))          => x.0.cmp(&y.0),
+            (Statement::Atom(x), Statement::Atom(y))                => by_name(x.0, y.0),
+            (Statement::Text(x), Statement::Text(y))                => x.0.cmp(&y.0),
+            (Statement::Sequence(x), Statement::Sequence(y))        => x.iter().cmp(y.iter()),
+            (Statement::List(x), Statement::List(y))                => x.items.iter().cmp(y.items.iter()).then(x.ispiped.cmp(&y.ispiped)),
+            (Statement::UList(x), Statement::UList(y))              => sorted(&x.items, x.ispiped).cmp(&sorted(&y.items, y.ispiped)).then(x.ispiped.cmp(&y.ispiped)),
+            (Statement::Object(Object((x, xp))), Statement::Object(Object((y, yp)))) => sorted(x, *xp).cmp(&sorted(y, *yp)).then(xp.cmp(yp)),
+            // compounds are ordered by arity, then name, then arguments
+            (Statement::Compound(x), Statement::Compound(y))        => x.arguments.len().cmp(&y.arguments.len())
+                .then_with(|| by_name(x.name.0, y.name.0))
+                .then_with(|| x.arguments.iter().cmp(y.arguments.iter())),
+            (Statement::Call(x), Statement::Call(y))                => x.function.name.cmp(&y.function.name).then_with(|| x.arguments.iter().cmp(y.arguments.iter())),
+            (Statement::BinaryOperator(x), Statement::BinaryOperator(y)) => x.symbol().cmp(y.symbol()).then_with(|| x.operands().cmp(&y.operands())),
+            (Statement::UnaryOperator(x), Statement::UnaryOperator(y))   => x.symbol().cmp(y.symbol()).then_with(|| x.operand().cmp(y.operand())),
+            (Statement::Spread(x), Statement::Spread(y))            => x.cmp(y),
+            (x, y) => numeric(x, y).unwrap_or(Ordering::Equal), // numbers, or both empty
+        });
+    }
+}
+
+// orders two symbols by their names, which are only looked up if the symbols differ.
+fn by_name(x : Symbol, y : Symbol) -> Ordering {
+    if x == y {
+        return Ordering::Equal;
+    }
+    return x.name().cmp(&y.name());
+}
+
+// the items of an unordered collection in the standard order, the tail of a piped collection (its last item) stays last.
+fn sorted(items : &Sequence, ispiped : bool) -> Vec<&Statement> {
+    let (items, tail) = match (ispiped, items.items.split_last()) {
+        (true, Some((tail, items))) => (items, Some(tail)),
+        _ => (&items.items[..], None),
+    };
+    let mut sorted : Vec<&Statement> = items.iter().collect();
+    sorted.sort();
+    sorted.extend(tail);
+    return sorted;
+}
+
+// the position of each kind of statement in the standard order, numbers of any type have the same rank.
+fn rank(statement : &Statement) -> usize {
+    return match statement {
+        Statement::Variable(_) => 0,
+        Statement::Boolean(_) => 1,
+        Statement::Integer(_) | Statement::Float(_) | Statement::Rational(_) => 2,
+        Statement::Atom(_) => 3,
+        Statement::Text(_) => 4,
+        Statement::Sequence(_) => 5,
+        Statement::List(_) => 6,
+        Statement::UList(_) => 7,
+        Statement::Object(_) => 8,
+        Statement::Compound(_) => 9,
+        Statement::Call(_) => 10,
+        Statement::BinaryOperator(_) => 11,
+        Statement::UnaryOperator(_) => 12,
+        Statement::Spread(_) => 13,
+        Statement::Empty => 14,
+    };
+}
+
+// orders two numbers by value, NaN comes last. If the values are the same a float comes first, then a rational.
+fn numeric(x : &Statement, y : &Statement) -> Option<Ordering> {
+    let ordering = match (x, y) {
+        (Statement::Float(xx), _) if xx.0.is_nan() => return Some(if matches!(y, Statement::Float(yy) if yy.0.is_nan()) { Ordering::Equal } else { Ordering::Greater }),
+        (_, Statement::Float(yy)) if yy.0.is_nan() => return Some(Ordering::Less),
+        (Statement::Integer(xx), Statement::Integer(yy))    => xx.compare(yy),
+        (Statement::Float(xx), Statement::Float(yy))        => xx.compare(yy),
+        (Statement::Rational(xx), Statement::Rational(yy))  => xx.compare(yy),
+        (Statement::Integer(xx), Statement::Rational(yy))   => Rational::from(*xx).compare(yy),
+        (Statement::Rational(xx), Statement::Integer(yy))   => xx.compare(&Rational::from(*yy)),
+        (Statement::Integer(xx), Statement::Float(yy))      => xx.compare(yy),
+        (Statement::Float(xx), Statement::Integer(yy))      => xx.compare(yy),
+        (Statement::Rational(xx), Statement::Float(yy))     => xx.to_float().partial_cmp(&yy.0),
+        (Statement::Float(xx), Statement::Rational(yy))     => xx.0.partial_cmp(&yy.to_float()),
+        _ => return None,
+    };
+    let kind = |x : &Statement| match x { Statement::Float(_) => 0, Statement::Rational(_) => 1, _ => 2 };
+    return Some(ordering?.then(kind(x).cmp(&kind(y))));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashSet};
+    use crate::interpret::tests::expressions;
+    use crate::statement::{Atom, Compound, Sequence, AsStatement};
+
+    #[test]
+    fn unordered_collections_as_keys() {
+        let (statements, _) = expressions(&["{1, 2}", "{2, 1}", "{a : 1, b : 2}", "{b : 2, a : 1}", "{1, 2 | T}", "{2, 1 | T}", "{1, T | {2}}"]);
+        assert_eq!(statements[0], statements[1]);
+        assert_eq!(statements[0].cmp(&statements[1]), std::cmp::Ordering::Equal);
+        assert_eq!(statements[2], statements[3]);
+        assert_eq!(statements[4], statements[5]);
+        assert_ne!(statements[4], statements[6]); // the tail is not one of the items
+        assert_eq!(statements.iter().collect::<HashSet<_>>().len(), 4);
+        assert_eq!(statements.iter().collect::<BTreeSet<_>>().len(), 4);
+    }
+
+    #[test]
+    fn atoms_by_name() {
+        // `zulu_order` is interned before `alpha_order`
+        let (mut items, mut symbols) = expressions(&["zulu_order", "3", "alpha_order", "\"b\"", "mike_order", "1.0", "1", "[a]", "false"]);
+        let mut compound = |name : &str, arguments : &[&str]| {
+            let arguments = arguments.iter().map(|x| Atom(symbols.intern(x)).as_statement()).collect();
+            return Compound { name : Atom(symbols.intern(name)), arguments : Sequence::new(arguments) }.as_statement();
+        };
+        items.extend([compound("f", &["zulu_order"]), compound("g", &["a"]), compound("f", &["alpha_order"]), compound("f", &["a", "a"])]);
+        items.sort();
+        let sorted : Vec<String> = items.iter().map(|x| symbols.display(x).to_string()).collect();
+        assert_eq!(sorted, ["false", "1.0", "1", "3", "alpha_order", "mike_order", "zulu_order", "\"b\"", "[a]", "f(alpha_order)", "f(zulu_order)", "g(a)", "f(a, a)"]);
+    }
+}
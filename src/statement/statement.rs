@@ -1,92 +1,241 @@
 
-use std::{rc::Rc, collections::HashMap};
-
-use crate::statement::{BinaryOperator, UnaryOperator, Frame, Sequence, List, UList, Object};
-
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct Float(pub f32);
-
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct Integer(pub i32);
+use std::cell::RefCell;
+use crate::statement::{BinaryOperator, UnaryOperator, Sequence, List, UList, Object, Call, Symbol, EvaluationError, Numbers};
+use crate::statement::symbol::ANONYMOUS;
+
+// the representation of numbers, programs run with 64-bit integers and floats. Dividing integers truncates unless the program
+// divides exactly (see `Numbers`), then it produces a fraction.
+pub type IntegerValue = i64;
+pub type FloatValue = f64;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Float(pub FloatValue); // equality and hashing are defined with the standard order, see `order.rs`
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Integer(pub IntegerValue);
+
+/// An exact fraction, it is kept in lowest terms with a positive denominator. Fractions are produced by dividing integers
+/// in a program that divides exactly, see `Division`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Rational {
+    pub numerator : IntegerValue,
+    pub denominator : IntegerValue,
+}
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Boolean(pub bool);
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Atom(pub String);
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Atom(pub Symbol);
+
+/// A string of text, written between double quotes (e.g. `"Traffic Light"`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Text(pub String);
 
-/*
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Compound {
-    pub name : String,
+    pub name : Atom,
     pub arguments : Sequence,
-} */
+}
 
 
-#[derive(Debug, Clone, PartialEq)]
+/// A variable is identified by its slot, which numbers the variables of a goal in order of their first occurrence.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Variable {
-    pub name : String
+    pub name : Symbol,
+    pub slot : Option<usize>, // anonymous variables have no slot
 }
 
 impl Variable {
+    pub fn anonymous() -> Self {
+        return Variable { name : ANONYMOUS, slot : None };
+    }
+
     pub fn is_anonymous(&self) -> bool {
-        return self.name == "_";
+        return self.slot.is_none();
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+// statements carry no spans, each goal keeps where its operators and calls are written and an error while evaluating one
+// is located at the expression that raised it, or else at the condition or action that contains it (see `Goal::evaluate`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Statement {
     Float(Float),
     Integer(Integer),
+    Rational(Rational),
     Boolean(Boolean),
     Atom(Atom),
-    //Compound(Compound),
+    Text(Text),
+    Compound(Compound),
     Variable(Variable),
     BinaryOperator(BinaryOperator),
     UnaryOperator(UnaryOperator),
+    Call(Call),
     Sequence(Sequence),
     List(List),
     UList(UList),
     Object(Object),
+    Spread(Box<Statement>), // a capture of the remaining items of a collection, e.g. `*REST` in `[A, *REST]`
     Empty,
 }
 
-impl Default for Atom {
-    fn default() -> Self { Self(Default::default())}
-}
-
 impl Default for Variable {
-    fn default() -> Self { Self { ..Default::default() }}
+    fn default() -> Self { Variable::anonymous() }
 } 
 
 impl Statement {
-    pub fn evaluate(&self) -> Statement {
+    /// the value of this statement, operators and calls are applied and collections are evaluated item by item. Variables
+    /// should be substituted first (see `Frame::evaluate`), evaluating an unbound variable is an error. Numbers are compared
+    /// (e.g. by `=` and `!=`) and divided according to `numbers`, as they are when matching.
+    pub fn evaluate(&self, numbers : &Numbers) -> Result<Statement, EvaluationError> {
         let z = match self {
             Statement::Float(x) => Statement::Float(*x),
             Statement::Integer(x) => Statement::Integer(*x),
-            Statement::BinaryOperator(x) => x.evaluate(),
-            Statement::UnaryOperator(x) => x.evaluate(),
-            _ => unreachable!(),
+            Statement::Rational(x) => Statement::Rational(*x),
+            Statement::BinaryOperator(x) => x.evaluate(numbers)?,
+            Statement::UnaryOperator(x) => x.evaluate(numbers)?,
+            Statement::Call(x) => x.evaluate(numbers)?,
+            Statement::Variable(x) => return Err(EvaluationError::Unbound(*x)),
+            x => x.try_map(|y| y.evaluate(numbers))?,
         };
-        return z;
+        return Ok(z);
     }
-}
 
+    /// evaluates the parts of this statement that are ground, leaving its variables (and the expressions that contain them)
+    /// as they are, e.g. `[1 + 1, X + 1]` becomes `[2, X + 1]`.
+    pub fn evaluate_ground(&self, numbers : &Numbers) -> Result<Statement, EvaluationError> {
+        if self.is_ground() {
+            return self.evaluate(numbers);
+        }
+        return self.try_map(|x| x.evaluate_ground(numbers));
+    }
 
+    /// replaces each variable in this statement with the anonymous variable `_`.
+    pub fn anonymise(&self) -> Statement {
+        return match self {
+            Statement::Variable(_) => Variable::anonymous().as_statement(),
+            x => x.map(|y| y.anonymise()),
+        };
+    }
 
-// pattern matching
+    /// replaces the symbol of each atom and variable in this statement with `f` of it, e.g. to compare statements whose
+    /// names were interned by different `Symbols`.
+    pub fn translate<F : Fn(Symbol) -> Symbol>(&self, f : &F) -> Statement {
+        return match self {
+            Statement::Atom(x) => Atom(f(x.0)).as_statement(),
+            Statement::Variable(x) => Variable { name : f(x.name), slot : x.slot }.as_statement(),
+            Statement::Compound(x) => Compound { name : Atom(f(x.name.0)), arguments : Sequence::new(x.arguments.iter().map(|y| y.translate(f)).collect()) }.as_statement(),
+            x => x.map(|y| y.translate(f)),
+        };
+    }
 
-pub trait Ground {
-    fn ground(&self, other : &Statement) -> Option<&Self>;
-}
+    /// whether this statement contains no variables (anonymous or otherwise).
+    pub fn is_ground(&self) -> bool {
+        return match self {
+            Statement::Variable(_) => false,
+            x => x.children().iter().all(|y| y.is_ground()),
+        };
+    }
 
+    /// the statements directly contained in this statement.
+    pub fn children(&self) -> Vec<&Statement> {
+        return match self {
+            Statement::Compound(x) => x.arguments.iter().collect(),
+            Statement::BinaryOperator(x) => { let (lhs, rhs) = x.operands(); vec![lhs, rhs] },
+            Statement::UnaryOperator(x) => vec![x.operand()],
+            Statement::Call(x) => x.arguments.iter().collect(),
+            Statement::Sequence(x) => x.iter().collect(),
+            Statement::List(x) => x.items.iter().collect(),
+            Statement::UList(x) => x.items.iter().collect(),
+            Statement::Object(Object((x, _))) => x.iter().collect(),
+            Statement::Spread(x) => vec![x],
+            _ => Vec::new(),
+        };
+    }
 
+    /// applies `f` to each statement directly contained in this statement, producing a statement of the same kind.
+    pub fn map<F : Fn(&Statement) -> Statement>(&self, f : F) -> Statement {
+        let g = |x : &Sequence| Sequence::new(x.iter().map(&f).collect());
+        return match self {
+            Statement::Compound(x) => Compound { name : x.name, arguments : g(&x.arguments) }.as_statement(),
+            Statement::BinaryOperator(x) => Statement::BinaryOperator(x.map(&f)),
+            Statement::UnaryOperator(x) => Statement::UnaryOperator(x.map(&f)),
+            Statement::Call(x) => Call { function : x.function.clone(), arguments : g(&x.arguments) }.as_statement(),
+            Statement::Sequence(x) => g(x).as_statement(),
+            Statement::List(x) => List::new(g(&x.items), x.ispiped).as_statement(),
+            Statement::UList(x) => UList::new(g(&x.items), x.ispiped).as_statement(),
+            Statement::Object(Object((x, ispiped))) => Object((g(x), *ispiped)).as_statement(),
+            Statement::Spread(x) => Statement::Spread(Box::new(f(x))),
+            x => x.clone(),
+        };
+    }
 
+    /// applies `f` to each statement directly contained in this statement as `map` does, failing with the first error.
+    pub fn try_map<E, F : Fn(&Statement) -> Result<Statement, E>>(&self, f : F) -> Result<Statement, E> {
+        let error = RefCell::new(None);
+        let result = self.map(|x| f(x).unwrap_or_else(|e| { error.borrow_mut().get_or_insert(e); Statement::Empty }));
+        return match error.into_inner() {
+            Some(e) => Err(e),
+            None => Ok(result),
+        };
+    }
 
+    /// finds the first statement (this statement or one contained in it) that satisfies `predicate`.
+    pub fn find<F : Fn(&Statement) -> bool>(&self, predicate : &F) -> Option<&Statement> {
+        if predicate(self) {
+            return Some(self);
+        }
+        return self.children().into_iter().find_map(|x| x.find(predicate));
+    }
+
+    /// replaces `target` (which must be contained in this statement, it is compared by address) with `value`.
+    pub fn replace(&self, target : &Statement, value : &Statement) -> Statement {
+        if std::ptr::eq(self, target) {
+            return value.clone();
+        }
+        return self.map(|x| x.replace(target, value));
+    }
+
+    /// the number of spread captures directly contained in a collection, a piped tail counts as a spread capture.
+    pub fn spreads(&self) -> usize {
+        return match self {
+            Statement::List(x) => x.spreads(),
+            Statement::UList(x) => x.spreads(),
+            Statement::Object(x) => x.spreads(),
+            _ => 0,
+        };
+    }
+
+    /// replaces each spread of a collection of the same kind with its items, e.g. `[1, *[2, 3]]` becomes `[1, 2, 3]`.
+    pub fn splice(self) -> Statement {
+        return match self {
+            Statement::List(x) => List::new(splice(&x.items, |y| match y { Statement::List(z) if !z.ispiped => Some(&z.items), _ => None }), x.ispiped).as_statement(),
+            Statement::UList(x) => UList::new(splice(&x.items, |y| match y { Statement::UList(z) if !z.ispiped => Some(&z.items), _ => None }), x.ispiped).as_statement(),
+            Statement::Object(Object((x, ispiped))) => Object::new(splice(&x, |y| match y { Statement::Object(Object((z, false))) => Some(z), _ => None }), ispiped).as_statement(),
+            x => x,
+        };
+    }
+}
+
+fn splice<F : Fn(&Statement) -> Option<&Sequence>>(items : &Sequence, inner : F) -> Sequence {
+    let mut result = Vec::with_capacity(items.len());
+    for item in items.iter() {
+        match item {
+            Statement::Spread(x) if inner(x).is_some() => result.extend(inner(x).unwrap().iter().cloned()),
+            x => result.push(x.clone()),
+        }
+    }
+    return Sequence::new(result);
+}
+
+fn spreads(items : &Sequence, ispiped : bool) -> usize {
+    return items.iter().filter(|x| matches!(x, Statement::Spread(_))).count() + ispiped as usize;
+}
 
 
 
 // converting types to their corresponding Statement variants... could probably be done with a macro...
+#[allow(clippy::wrong_self_convention)]
 pub trait AsStatement {
     fn as_statement(self) -> Statement ;
 }
@@ -97,6 +246,12 @@ impl AsStatement for Variable {
     }
 }
 
+impl AsStatement for Compound {
+    fn as_statement(self) -> Statement {
+        return Statement::Compound(self);
+    }
+}
+
 impl AsStatement for Sequence {
     fn as_statement(self) -> Statement {
         return Statement::Sequence(self);
@@ -127,6 +282,18 @@ impl AsStatement for Atom {
     }
 }
 
+impl AsStatement for Text {
+    fn as_statement(self) -> Statement {
+        return Statement::Text(self);
+    }
+}
+
+impl AsStatement for Boolean {
+    fn as_statement(self) -> Statement {
+        return Statement::Boolean(self);
+    }
+}
+
 impl AsStatement for Float {
     fn as_statement(self) -> Statement {
         return Statement::Float(self);
@@ -139,19 +306,63 @@ impl AsStatement for Integer {
     }
 }
 
-impl AsStatement for f32 {
+impl AsStatement for Rational {
+    fn as_statement(self)  -> Statement  {
+        // a whole number is an integer
+        if self.denominator == 1 {
+            return Statement::Integer(Integer(self.numerator));
+        }
+        return Statement::Rational(self);
+    }
+}
+
+impl AsStatement for FloatValue {
     fn as_statement(self)  -> Statement  {
         return Statement::Float(Float(self));
     }
 }
 
-impl AsStatement for i32 {
+impl AsStatement for IntegerValue {
     fn as_statement(self)  -> Statement {
         return Statement::Integer(Integer(self));
     }
 }
 
+impl Rational {
+    /// the fraction `numerator / denominator` in lowest terms, `None` if the denominator is zero or the fraction overflows.
+    pub fn new(numerator : IntegerValue, denominator : IntegerValue) -> Option<Rational> {
+        if denominator == 0 {
+            return None;
+        }
+        let divisor = gcd(numerator, denominator);
+        let (numerator, denominator) = (numerator / divisor, denominator / divisor);
+        if denominator < 0 {
+            return Some(Rational { numerator : numerator.checked_neg()?, denominator : denominator.checked_neg()? });
+        }
+        return Some(Rational { numerator : numerator, denominator : denominator });
+    }
+
+    pub fn to_float(self) -> FloatValue {
+        return self.numerator as FloatValue / self.denominator as FloatValue;
+    }
+}
+
+// the greatest common divisor of `x` and `y` (one of which is not zero), the result is negative only if it is 2^63.
+fn gcd(x : IntegerValue, y : IntegerValue) -> IntegerValue {
+    let (mut x, mut y) = (x.unsigned_abs(), y.unsigned_abs());
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    return x as IntegerValue;
+}
+
 // Type conversions
+impl From<Integer> for Rational {
+    fn from(item: Integer) -> Self {
+        return Rational { numerator : item.0, denominator : 1 };
+    }
+}
+
 impl From<UList> for List {
     fn from(item: UList) -> Self {
         return List { items : item.items, ispiped : item.ispiped };
@@ -172,6 +383,24 @@ impl List {
     pub fn len(&self) -> usize {
         return self.items.len();
     }
+
+    pub fn is_empty(&self) -> bool {
+        return self.items.is_empty();
+    }
+
+    /// splits a piped collection into its items and its tail, e.g. [A, B | T] into (A, B) and T.
+    pub fn split(&self) -> (&[Statement], Option<&Statement>) {
+        if self.ispiped {
+            let (tail, items) = self.items.items.split_last().unwrap();
+            return (items, Some(tail));
+        }
+        return (&self.items.items, None);
+    }
+
+    /// the number of spread captures in this list, a piped tail counts as a spread capture.
+    pub fn spreads(&self) -> usize {
+        return spreads(&self.items, self.ispiped);
+    }
 }
 
 impl UList {
@@ -183,7 +412,41 @@ impl UList {
         return self.items.len();
     }
 
+    pub fn is_empty(&self) -> bool {
+        return self.items.is_empty();
+    }
+
+    /// splits a piped collection into its items and its tail, e.g. [A, B | T] into (A, B) and T.
+    pub fn split(&self) -> (&[Statement], Option<&Statement>) {
+        if self.ispiped {
+            let (tail, items) = self.items.items.split_last().unwrap();
+            return (items, Some(tail));
+        }
+        return (&self.items.items, None);
+    }
+
+    /// the number of spread captures in this ulist, a piped tail counts as a spread capture.
+    pub fn spreads(&self) -> usize {
+        return spreads(&self.items, self.ispiped);
+    }
 }
 
+impl Object {
+    /// an object from its entries, each entry is a (key, value) sequence.
+    pub fn new(items : Sequence, ispiped : bool) -> Self {
+        return Object((items, ispiped));
+    }
+
+    pub fn len(&self) -> usize {
+        return self.0.0.len();
+    }
 
+    pub fn is_empty(&self) -> bool {
+        return self.0.0.is_empty();
+    }
 
+    /// the number of spread captures in this object, a piped tail counts as a spread capture.
+    pub fn spreads(&self) -> usize {
+        return spreads(&self.0.0, self.0.1);
+    }
+}
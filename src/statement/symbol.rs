@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// An interned name, atoms and variables are compared (and hashed) by their symbol rather than by their name.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Symbol(pub u32);
+
+/// the symbol of the anonymous variable `_`, it is the first symbol in every `Symbols`.
+pub const ANONYMOUS : Symbol = Symbol(0);
+
+// the name of every symbol and the symbol of every name, whichever program interned it.
+struct Names {
+    names : Vec<Arc<str>>,
+    ids : HashMap<Arc<str>, Symbol>,
+}
+
+lazy_static::lazy_static! {
+    // symbols are numbered across programs so that the name of a symbol can be found without its program (e.g. to order 
+    // atoms by name).
+    static ref NAMES : RwLock<Names> = {
+        let anonymous : Arc<str> = Arc::from("_");
+        RwLock::new(Names { names : vec![anonymous.clone()], ids : HashMap::from([(anonymous, ANONYMOUS)]) })
+    };
+}
+
+impl Symbol {
+    /// the name that this symbol was interned for.
+    pub fn name(self) -> Arc<str> {
+        let names = NAMES.read().unwrap_or_else(|e| e.into_inner());
+        return names.names[self.0 as usize].clone();
+    }
+}
+
+/// Maps names to symbols and back. Each program owns the symbols used by its goals and the observations given to it, a
+/// name has the same symbol in every program that has it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbols {
+    names : HashMap<Symbol, Arc<str>>,
+    ids : HashMap<Arc<str>, Symbol>,
+}
+
+impl Default for Symbols {
+    fn default() -> Self { return Symbols::new(); }
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        let mut symbols = Symbols { names : HashMap::new(), ids : HashMap::new() };
+        symbols.intern("_");
+        return symbols;
+    }
+
+    /// gets the symbol for `name`, adding it to these symbols if `name` has not been seen before.
+    pub fn intern(&mut self, name : &str) -> Symbol {
+        if let Some(symbol) = self.ids.get(name) {
+            return *symbol;
+        }
+        let (name, symbol) = {
+            let mut names = NAMES.write().unwrap_or_else(|e| e.into_inner());
+            match names.ids.get_key_value(name) {
+                Some((name, symbol)) => (name.clone(), *symbol),
+                None => {
+                    let (name, symbol) : (Arc<str>, Symbol) = (Arc::from(name), Symbol(names.names.len() as u32));
+                    names.names.push(name.clone());
+                    names.ids.insert(name.clone(), symbol);
+                    (name, symbol)
+                },
+            }
+        };
+        self.names.insert(symbol, name.clone());
+        self.ids.insert(name, symbol);
+        return symbol;
+    }
+
+    /// gets the symbol for `name` if it has been seen before.
+    pub fn get(&self, name : &str) -> Option<Symbol> {
+        return self.ids.get(name).copied();
+    }
+
+    pub fn name(&self, symbol : Symbol) -> &str {
+        return &self.names[&symbol];
+    }
+
+    pub fn len(&self) -> usize {
+        return self.names.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.names.is_empty();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_symbol_per_name() {
+        let mut symbols = Symbols::new();
+        let names = ["move", "X", "move", "_", "Traffic"];
+        let interned : Vec<Symbol> = names.iter().map(|x| symbols.intern(x)).collect();
+        assert_eq!(interned[0], interned[2]);
+        assert_eq!(interned[3], ANONYMOUS);
+        assert_eq!(symbols.len(), 4);
+        // each symbol has its name
+        assert!(names.iter().zip(interned.iter()).all(|(x, y)| symbols.name(*y) == *x && symbols.get(x) == Some(*y)));
+        assert_eq!(symbols.get("stop"), None);
+        // other symbols have the same names, and a name has the same symbol in each
+        let mut other = Symbols::new();
+        assert_eq!(other.intern("Traffic"), interned[4]);
+        assert!(names.iter().zip(interned.iter()).all(|(x, y)| y.name().as_ref() == *x));
+        assert_eq!((other.len(), other.get("move")), (2, None));
+    }
+}